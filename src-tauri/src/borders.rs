@@ -0,0 +1,166 @@
+use crate::gamestate::{self, get_db, get_name};
+use crate::graph::{self, Graph};
+use serde_json::{json, Value};
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+
+#[derive(serde::Serialize, PartialEq, Debug)]
+pub struct BorderReport {
+	pub country_id: i64,
+	pub name: Option<String>,
+	pub systems: usize,
+	pub border_systems: Vec<BorderSystem>,
+	/// Systems whose loss would cut the country's territory in two or more
+	pub chokepoints: Vec<i64>,
+	/// The number of lanes leading into the territory, by the country owning the other end
+	pub entry_lanes: BTreeMap<i64, usize>,
+	/// The number of lanes leading into the territory from unowned systems
+	pub unowned_entry_lanes: usize,
+}
+
+/// An owned system with at least one lane to a system the country doesn't own
+#[derive(serde::Serialize, PartialEq, Debug)]
+pub struct BorderSystem {
+	pub system_id: i64,
+	/// The countries owning the systems across the border
+	pub neighbors: Vec<i64>,
+	pub touches_unowned: bool,
+}
+
+/// What the graph needs, plus system ownership and country names
+pub fn get_filter() -> Value {
+	let mut filter = graph::get_filter();
+	filter["galactic_object"]["*"]["starbases"] = json!(true);
+	filter["starbase_mgr"] = json!(true);
+	filter["ships"] = json!({ "*": { "fleet": true } });
+	filter["country"]["*"]["name"] = json!(true);
+	filter["country"]["*"]["fleets_manager"] = json!(true);
+	return filter;
+}
+
+/// A report for every country owning at least one system
+pub fn get_border_reports(state: &Value, include_bypasses: bool) -> Vec<BorderReport> {
+	let graph = Graph::from_state(state, include_bypasses);
+	let owners = gamestate::get_system_owners(state);
+	return get_db(state, &["country"])
+		.into_iter()
+		.filter_map(|(id, country)| {
+			let mut report = build_report(&graph, &owners, id)?;
+			report.name = get_name(country);
+			Some(report)
+		})
+		.collect();
+}
+
+fn build_report(
+	graph: &Graph,
+	owners: &HashMap<i64, i64>,
+	country_id: i64,
+) -> Option<BorderReport> {
+	let territory: HashSet<i64> = owners
+		.iter()
+		.filter(|(_, owner)| **owner == country_id)
+		.map(|(system, _)| *system)
+		.collect();
+	if territory.is_empty() {
+		return None;
+	}
+
+	let mut border_systems = vec![];
+	let mut entry_lanes = BTreeMap::new();
+	let mut unowned_entry_lanes = 0;
+	for system in territory.iter().copied().collect::<BTreeSet<i64>>() {
+		let mut neighbors = BTreeSet::new();
+		let mut touches_unowned = false;
+		for neighbor in graph.get_neighbors(system) {
+			match owners.get(&neighbor) {
+				Some(owner) if *owner == country_id => continue,
+				Some(owner) => {
+					neighbors.insert(*owner);
+					*entry_lanes.entry(*owner).or_default() += 1;
+				}
+				None => {
+					touches_unowned = true;
+					unowned_entry_lanes += 1;
+				}
+			}
+		}
+		if touches_unowned || !neighbors.is_empty() {
+			border_systems.push(BorderSystem {
+				system_id: system,
+				neighbors: neighbors.into_iter().collect(),
+				touches_unowned,
+			});
+		}
+	}
+
+	return Some(BorderReport {
+		country_id,
+		name: None,
+		systems: territory.len(),
+		border_systems,
+		chokepoints: graph
+			.get_subgraph(&territory)
+			.get_chokepoints()
+			.articulation_points,
+		entry_lanes,
+		unowned_entry_lanes,
+	});
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::gamestate::tests::{parse_save, sample_state};
+
+	#[test]
+	fn test_build_report() {
+		// a chain 0 - 1 - 2 - 3 - 4 with a branch 1 - 5, owned 0 0 0 1 - 2
+		let state = parse_save(
+			"galactic_object={
+				0={ hyperlane={ { to=1 length=10 } } }
+				1={ hyperlane={ { to=0 length=10 } { to=2 length=10 } { to=5 length=10 } } }
+				2={ hyperlane={ { to=1 length=10 } { to=3 length=10 } } }
+				3={ hyperlane={ { to=2 length=10 } { to=4 length=10 } } }
+				4={ hyperlane={ { to=3 length=10 } } }
+				5={ hyperlane={ { to=1 length=10 } } }
+			}",
+		);
+		let graph = Graph::from_state(&state, false);
+		let owners = HashMap::from([(0, 0), (1, 0), (2, 0), (3, 1), (5, 2)]);
+
+		let report = build_report(&graph, &owners, 0).unwrap();
+		assert_eq!(report.systems, 3);
+		assert_eq!(
+			report.border_systems,
+			vec![
+				BorderSystem {
+					system_id: 1,
+					neighbors: vec![2],
+					touches_unowned: false,
+				},
+				BorderSystem {
+					system_id: 2,
+					neighbors: vec![1],
+					touches_unowned: false,
+				},
+			]
+		);
+		assert_eq!(report.chokepoints, vec![1]);
+		assert_eq!(report.entry_lanes, BTreeMap::from([(1, 1), (2, 1)]));
+		assert_eq!(report.unowned_entry_lanes, 0);
+
+		let report = build_report(&graph, &owners, 1).unwrap();
+		assert!(report.border_systems[0].touches_unowned);
+		assert_eq!(report.unowned_entry_lanes, 1);
+		assert!(build_report(&graph, &owners, 3).is_none());
+	}
+
+	#[test]
+	fn test_get_border_reports() {
+		let reports = get_border_reports(&sample_state(), false);
+		assert_eq!(reports.len(), 2);
+		assert_eq!(reports[0].name.as_deref(), Some("United Nations of Earth"));
+		assert_eq!(reports[0].entry_lanes, BTreeMap::from([(1, 1)]));
+		assert_eq!(reports[1].border_systems[0].system_id, 2);
+	}
+}
@@ -0,0 +1,113 @@
+use crate::vfs::{get_files_matching_filter, FileFilter};
+use crate::StellarisSave;
+use rayon::prelude::*;
+use serde_json::Value;
+use std::ffi::OsString;
+use std::path::PathBuf;
+
+/// The saves in a campaign dir (eg save games/unitednationsofearth_1234567), in game date order
+///
+/// Manual saves and autosaves of the same day are duplicates, so only the most recently written one is kept
+pub fn get_campaign_saves(dir: &PathBuf) -> anyhow::Result<Vec<StellarisSave>> {
+	let mut saves: Vec<StellarisSave> =
+		get_files_matching_filter(dir, &FileFilter::Extension(OsString::from("sav")), 1)?
+			.iter()
+			.map(StellarisSave::from_path_or_default)
+			.filter(|save| !save.date.is_empty())
+			.collect();
+	saves.sort_by(|a, b| a.date.cmp(&b.date).then(b.modified.cmp(&a.modified)));
+	saves.dedup_by(|a, b| a.date == b.date);
+	return Ok(saves);
+}
+
+/// Parses every save in the campaign and maps its game state with f, in game date order
+///
+/// Game states are dropped as soon as they're mapped, so only a few are in memory at once;
+/// saves that fail to load are skipped
+pub fn map_campaign_saves<T, F>(
+	dir: &PathBuf,
+	filter: &Value,
+	f: F,
+) -> anyhow::Result<Vec<(StellarisSave, T)>>
+where
+	T: Send,
+	F: Fn(&Value) -> T + Sync,
+{
+	let saves = get_campaign_saves(dir)?;
+	let results = saves
+		.into_par_iter()
+		.filter_map(
+			|save| match crate::get_stellaris_save(save.path.clone(), filter.clone()) {
+				Ok(state) => {
+					let result = f(&state);
+					Some((save, result))
+				}
+				Err(err) => {
+					println!("failed to load {}: {}", save.path, err);
+					None
+				}
+			},
+		)
+		.collect();
+	return Ok(results);
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+	use super::*;
+	use std::fs;
+	use std::io::Write;
+	use std::path::Path;
+	use tempfile::TempDir;
+
+	pub(crate) fn write_save(dir: &Path, file_name: &str, date: &str, gamestate: &str) {
+		let mut zip = zip::ZipWriter::new(fs::File::create(dir.join(file_name)).unwrap());
+		let options = zip::write::FileOptions::default();
+		zip.start_file("meta", options).unwrap();
+		write!(zip, "name=\"Test Campaign\"\ndate=\"{}\"\n", date).unwrap();
+		zip.start_file("gamestate", options).unwrap();
+		zip.write_all(gamestate.as_bytes()).unwrap();
+		zip.finish().unwrap();
+	}
+
+	#[test]
+	fn test_map_campaign_saves() {
+		let dir = TempDir::new().unwrap();
+		write_save(
+			dir.path(),
+			"autosave_2210.01.01.sav",
+			"2210.01.01",
+			"date=\"2210.01.01\"",
+		);
+		write_save(
+			dir.path(),
+			"2200.01.01.sav",
+			"2200.01.01",
+			"date=\"2200.01.01\"",
+		);
+		write_save(
+			dir.path(),
+			"autosave_2200.01.01.sav",
+			"2200.01.01",
+			"date=\"2200.01.01\"",
+		);
+		fs::write(dir.path().join("broken.sav"), "not a zip").unwrap();
+
+		let saves = get_campaign_saves(&dir.path().to_path_buf()).unwrap();
+		let dates: Vec<&str> = saves.iter().map(|save| save.date.as_str()).collect();
+		assert_eq!(dates, vec!["2200.01.01", "2210.01.01"]);
+
+		let results = map_campaign_saves(&dir.path().to_path_buf(), &Value::Bool(true), |state| {
+			state["date"].as_str().map(String::from)
+		})
+		.unwrap();
+		let dates: Vec<Option<String>> = results.into_iter().map(|(_, date)| date).collect();
+		assert_eq!(
+			dates,
+			vec![
+				Some(String::from("2200.01.01")),
+				Some(String::from("2210.01.01"))
+			]
+		);
+	}
+}
@@ -0,0 +1,87 @@
+use crate::sqlite;
+use std::path::Path;
+
+const USAGE: &str = "Usage:
+	stellarmaps                                       start the app
+	stellarmaps export-sqlite <save.sav> <out.db>     write the save's countries, systems, planets etc to SQLite";
+
+/// Runs the subcommand named by the arguments, returning its exit code
+///
+/// Returns None if there is no subcommand and the app should start as usual
+/// (unknown arguments are ignored, since launchers and OSes sometimes pass their own)
+pub fn run(args: &[String]) -> Option<i32> {
+	let command = match args.get(1).map(String::as_str) {
+		Some(command @ ("export-sqlite" | "help" | "--help" | "-h")) => command,
+		_ => return None,
+	};
+	attach_parent_console();
+	let result = match command {
+		"export-sqlite" => export_sqlite(&args[2..]),
+		_ => {
+			println!("{}", USAGE);
+			Ok(())
+		}
+	};
+	return Some(match result {
+		Ok(()) => 0,
+		Err(err) => {
+			eprintln!("{}", err);
+			1
+		}
+	});
+}
+
+/// Release builds on Windows use the GUI subsystem and start without a console,
+/// so output would be lost unless we attach to the terminal that ran us
+#[cfg(windows)]
+fn attach_parent_console() {
+	const ATTACH_PARENT_PROCESS: u32 = u32::MAX;
+	#[link(name = "kernel32")]
+	extern "system" {
+		fn AttachConsole(process_id: u32) -> i32;
+	}
+	// fails harmlessly if there is already a console (debug builds) or no parent console
+	unsafe {
+		AttachConsole(ATTACH_PARENT_PROCESS);
+	}
+}
+
+#[cfg(not(windows))]
+fn attach_parent_console() {}
+
+fn export_sqlite(args: &[String]) -> anyhow::Result<()> {
+	let (save_path, db_path) = match args {
+		[save_path, db_path] => (save_path, db_path),
+		_ => anyhow::bail!("{}", USAGE),
+	};
+	let state = crate::get_stellaris_save(save_path.clone(), sqlite::get_filter())?;
+	sqlite::export_to_file(&state, Path::new(db_path))?;
+	println!("exported {} to {}", save_path, db_path);
+	return Ok(());
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn args(args: &[&str]) -> Vec<String> {
+		return args.iter().map(|arg| arg.to_string()).collect();
+	}
+
+	#[test]
+	fn test_run() {
+		assert_eq!(run(&args(&["stellarmaps"])), None);
+		assert_eq!(run(&args(&["stellarmaps", "-psn_0_12345"])), None);
+		assert_eq!(run(&args(&["stellarmaps", "--help"])), Some(0));
+		assert_eq!(run(&args(&["stellarmaps", "export-sqlite"])), Some(1));
+		assert_eq!(
+			run(&args(&[
+				"stellarmaps",
+				"export-sqlite",
+				"missing.sav",
+				"out.db"
+			])),
+			Some(1)
+		);
+	}
+}
@@ -0,0 +1,159 @@
+use crate::gamestate::{get_i64, get_i64_list, get_list, get_str};
+use serde_json::{json, Value};
+
+/// Used for "no country", eg when there's no custodian
+const NO_COUNTRY: i64 = 4294967295;
+
+#[derive(serde::Serialize, PartialEq, Debug)]
+pub struct GalacticCommunity {
+	pub members: Vec<i64>,
+	pub council: Vec<i64>,
+	pub custodian: Option<i64>,
+	/// Set once the custodian has formed the Galactic Imperium
+	pub emperor: Option<i64>,
+	/// The resolution being voted on
+	pub voting: Option<Resolution>,
+	pub proposed: Vec<Resolution>,
+	/// Oldest first, then any without a date in save order
+	pub passed: Vec<Resolution>,
+}
+
+#[derive(serde::Serialize, PartialEq, Debug)]
+pub struct Resolution {
+	/// The id in the save's `resolutions`, if it was saved as a reference
+	pub resolution_id: Option<i64>,
+	/// Eg "resolution_galacticstudies_engineering_ai_research"
+	#[serde(rename = "type")]
+	pub resolution_type: Option<String>,
+	pub date: Option<String>,
+	pub proposer: Option<i64>,
+}
+
+pub fn get_filter() -> Value {
+	return json!({
+		"galactic_community": true,
+		"resolutions": true,
+	});
+}
+
+/// None until the galactic community has formed
+pub fn get_galactic_community(state: &Value) -> Option<GalacticCommunity> {
+	let community = state.get("galactic_community")?;
+	let members = get_i64_list(community, "members");
+	if members.is_empty() {
+		return None;
+	}
+	let mut passed: Vec<Resolution> = get_list(community, "passed")
+		.into_iter()
+		.filter_map(|resolution| get_resolution(state, resolution))
+		.collect();
+	// stable, so resolutions passed on the same day keep their save order
+	passed.sort_by_key(|resolution| (resolution.date.is_none(), resolution.date.clone()));
+	return Some(GalacticCommunity {
+		members,
+		council: get_i64_list(community, "council"),
+		custodian: get_country(community, "custodian"),
+		emperor: get_country(community, "emperor"),
+		voting: community
+			.get("voting")
+			.and_then(|resolution| get_resolution(state, resolution)),
+		proposed: get_list(community, "proposed")
+			.into_iter()
+			.filter_map(|resolution| get_resolution(state, resolution))
+			.collect(),
+		passed,
+	});
+}
+
+fn get_country(value: &Value, key: &str) -> Option<i64> {
+	return get_i64(value, key).filter(|id| *id >= 0 && *id != NO_COUNTRY);
+}
+
+/// Resolutions are saved either as ids into the top-level `resolutions`, or inline
+fn get_resolution(state: &Value, value: &Value) -> Option<Resolution> {
+	let (resolution_id, resolution) = match value {
+		Value::Number(_) => {
+			let id = value.as_i64().filter(|id| *id >= 0 && *id != NO_COUNTRY)?;
+			let resolution = state
+				.get("resolutions")
+				.and_then(|resolutions| resolutions.get(id.to_string()))
+				.filter(|resolution| resolution.is_object());
+			(Some(id), resolution)
+		}
+		Value::Object(_) => (None, Some(value)),
+		_ => return None,
+	};
+	return Some(Resolution {
+		resolution_id,
+		resolution_type: resolution
+			.and_then(|r| get_str(r, "type").or_else(|| get_str(r, "resolution")))
+			.map(String::from),
+		date: resolution
+			.and_then(|r| get_str(r, "date"))
+			.map(String::from),
+		proposer: resolution.and_then(|r| get_country(r, "proposer")),
+	});
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::gamestate::tests::parse_save;
+
+	#[test]
+	fn test_get_galactic_community() {
+		assert_eq!(get_galactic_community(&parse_save("")), None);
+		assert_eq!(
+			get_galactic_community(&parse_save("galactic_community={ members={ } }")),
+			None
+		);
+
+		let state = parse_save(
+			"galactic_community={
+				members={ 0 1 3 }
+				council={ 0 }
+				custodian=0
+				emperor=4294967295
+				voting=12
+				proposed={ 13 }
+				passed={ 12 11 { type=resolution_galacticstudies_engineering_ai_research date=\"2260.02.01\" } 10 }
+			}
+			resolutions={
+				10={ type=resolution_rulesofwar_independence_guarantee date=\"2255.06.01\" proposer=1 }
+				11={ type=resolution_ecology_environmental_control_board date=\"2270.01.01\" proposer=0 }
+				12={ type=resolution_custodian_extend_term proposer=0 }
+				13=none
+			}",
+		);
+		let community = get_galactic_community(&state).unwrap();
+		assert_eq!(community.council, vec![0]);
+		assert_eq!((community.custodian, community.emperor), (Some(0), None));
+		assert_eq!(
+			community.voting.unwrap().resolution_type.as_deref(),
+			Some("resolution_custodian_extend_term")
+		);
+		assert_eq!(
+			community.proposed,
+			vec![Resolution {
+				resolution_id: Some(13),
+				resolution_type: None,
+				date: None,
+				proposer: None,
+			}]
+		);
+		let passed: Vec<(Option<i64>, Option<&str>)> = community
+			.passed
+			.iter()
+			.map(|r| (r.resolution_id, r.date.as_deref()))
+			.collect();
+		assert_eq!(
+			passed,
+			vec![
+				(Some(10), Some("2255.06.01")),
+				(None, Some("2260.02.01")),
+				(Some(11), Some("2270.01.01")),
+				(Some(12), None),
+			]
+		);
+	}
+}
@@ -0,0 +1,149 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+pub const APP_IDENTIFIER: &str = "games.michaelmakes.stellarmaps";
+pub const DEFAULT_LANGUAGE: &str = "l_english";
+
+/// Backend settings, stored next to the frontend's settings.json in the app config dir
+///
+/// Every field is optional, so the file can be edited by hand and missing fields use defaults
+#[derive(serde::Serialize, serde::Deserialize, Default, Clone, PartialEq, Debug)]
+#[serde(default)]
+pub struct Config {
+	pub install_path: Option<PathBuf>,
+	pub user_data_path: Option<PathBuf>,
+	/// Searched for saves in addition to Steam Cloud and the user data dir
+	pub save_dirs: Vec<PathBuf>,
+	/// Mod dirs loaded after the launcher's enabled mods, eg mods in development
+	pub extra_mod_dirs: Vec<PathBuf>,
+	/// Stellaris language key, eg "l_english", used when a loc command isn't given one
+	pub language: Option<String>,
+	/// Used to fill in keys missing from language
+	pub fallback_languages: Vec<String>,
+	pub cache: CacheConfig,
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Clone, PartialEq, Debug)]
+#[serde(default)]
+pub struct CacheConfig {
+	pub enabled: bool,
+	/// Defaults to the platform cache dir
+	pub dir: Option<PathBuf>,
+}
+
+impl Default for CacheConfig {
+	fn default() -> Self {
+		return CacheConfig {
+			enabled: true,
+			dir: None,
+		};
+	}
+}
+
+impl Config {
+	/// The language to load when the caller doesn't pick one, English if unset
+	pub fn get_language(&self) -> String {
+		return self
+			.language
+			.clone()
+			.unwrap_or_else(|| String::from(DEFAULT_LANGUAGE));
+	}
+}
+
+impl CacheConfig {
+	/// None if caching is disabled
+	pub fn get_dir(&self) -> Option<PathBuf> {
+		if !self.enabled {
+			return None;
+		}
+		return self
+			.dir
+			.clone()
+			.or_else(|| dirs::cache_dir().map(|dir| dir.join(APP_IDENTIFIER)));
+	}
+}
+
+/// Same directory as tauri's $APPCONFIG, so the config is also available without a tauri app
+pub fn get_config_path() -> Option<PathBuf> {
+	return dirs::config_dir().map(|dir| dir.join(APP_IDENTIFIER).join("config.json"));
+}
+
+pub fn load_config() -> Config {
+	return match get_config_path() {
+		Some(path) => load_config_from(&path),
+		None => Config::default(),
+	};
+}
+
+pub fn save_config(config: &Config) -> anyhow::Result<()> {
+	let path = get_config_path().ok_or(anyhow::anyhow!("No config dir on this platform"))?;
+	return save_config_to(&path, config);
+}
+
+/// Missing or invalid config files are treated as empty, since every setting is optional
+fn load_config_from(path: &Path) -> Config {
+	if !path.exists() {
+		return Config::default();
+	}
+	return fs::read_to_string(path)
+		.map_err(anyhow::Error::from)
+		.and_then(|content| Ok(serde_json::from_str(&content)?))
+		.unwrap_or_else(|err| {
+			println!("failed to read config {}: {}", path.display(), err);
+			Config::default()
+		});
+}
+
+fn save_config_to(path: &Path, config: &Config) -> anyhow::Result<()> {
+	if let Some(dir) = path.parent() {
+		fs::create_dir_all(dir)?;
+	}
+	// write then rename, so a crash mid-write can't leave a truncated config
+	let tmp_path = path.with_extension("json.tmp");
+	fs::write(&tmp_path, serde_json::to_string_pretty(config)?)?;
+	fs::rename(&tmp_path, path)?;
+	return Ok(());
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use tempfile::TempDir;
+
+	#[test]
+	fn test_round_trip() {
+		let dir = TempDir::new().unwrap();
+		let path = dir.path().join("nested").join("config.json");
+		let config = Config {
+			install_path: Some(PathBuf::from("/games/Stellaris")),
+			extra_mod_dirs: vec![PathBuf::from("/dev/my_mod")],
+			language: Some(String::from("l_simp_chinese")),
+			fallback_languages: vec![String::from("l_english")],
+			cache: CacheConfig {
+				enabled: false,
+				dir: None,
+			},
+			..Default::default()
+		};
+		save_config_to(&path, &config).unwrap();
+		assert_eq!(load_config_from(&path), config);
+	}
+
+	#[test]
+	fn test_partial_and_invalid_files_use_defaults() {
+		let dir = TempDir::new().unwrap();
+		let path = dir.path().join("config.json");
+		fs::write(&path, r#"{ "language": "l_japanese" }"#).unwrap();
+		let config = load_config_from(&path);
+		assert_eq!(config.get_language(), "l_japanese");
+		assert!(config.cache.enabled);
+
+		fs::write(&path, "not json").unwrap();
+		assert_eq!(load_config_from(&path), Config::default());
+		assert_eq!(load_config_from(&path).get_language(), DEFAULT_LANGUAGE);
+		assert_eq!(
+			load_config_from(&dir.path().join("missing.json")),
+			Config::default()
+		);
+	}
+}
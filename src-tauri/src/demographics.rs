@@ -0,0 +1,397 @@
+use crate::gamestate::{self, get_db, get_f64, get_i64, get_name, get_str};
+use crate::lexer::Token;
+use crate::parser;
+use crate::vfs::{DataFs, FileFilter};
+use logos::Logos;
+use serde_json::{json, Value};
+use std::collections::{BTreeMap, HashMap};
+use std::ffi::OsString;
+use std::path::Path;
+
+#[derive(serde::Serialize, PartialEq, Debug)]
+pub struct DemographicsReport {
+	/// The species counted anywhere in the report
+	pub species: BTreeMap<i64, SpeciesSummary>,
+	pub systems: BTreeMap<i64, Demographics>,
+	/// By planet owner; unowned planets (eg pre-sapient homeworlds) are only counted per system
+	pub countries: BTreeMap<i64, Demographics>,
+}
+
+#[derive(serde::Serialize, PartialEq, Debug)]
+pub struct SpeciesSummary {
+	pub name: Option<String>,
+	/// Eg "HUM" or "ROBOT"
+	pub class: Option<String>,
+}
+
+#[derive(serde::Serialize, Default, PartialEq, Debug)]
+pub struct Demographics {
+	pub total: i64,
+	pub species: BTreeMap<i64, i64>,
+	/// Eg "ethic_xenophile"; pops without an ethic (eg robots) aren't counted
+	pub ethics: BTreeMap<String, i64>,
+	/// Eg "ruler", "specialist", "worker" or "slave"
+	pub strata: BTreeMap<String, i64>,
+	/// Employed pops by their job's category, eg "specialist"; unemployed pops aren't counted
+	pub job_categories: BTreeMap<String, i64>,
+}
+
+impl Demographics {
+	fn add(&mut self, pop: &PopCount) {
+		self.total += pop.size;
+		if let Some(species) = pop.species {
+			*self.species.entry(species).or_default() += pop.size;
+		}
+		for (counts, key) in [
+			(&mut self.ethics, pop.ethic),
+			(&mut self.strata, pop.stratum),
+			(&mut self.job_categories, pop.job_category),
+		] {
+			if let Some(key) = key {
+				*counts.entry(key.to_string()).or_default() += pop.size;
+			}
+		}
+	}
+
+	fn add_job(&mut self, job: &JobCount) {
+		*self
+			.job_categories
+			.entry(job.category.to_string())
+			.or_default() += job.workforce;
+	}
+}
+
+/// One pop (before 4.0) or pop group (4.0 and later)
+struct PopCount<'a> {
+	planet: i64,
+	size: i64,
+	species: Option<i64>,
+	ethic: Option<&'a str>,
+	stratum: Option<&'a str>,
+	/// Only set before 4.0, when each pop records its own job
+	job_category: Option<&'a str>,
+}
+
+/// The pops working one job type on one planet (4.0 and later)
+struct JobCount<'a> {
+	planet: i64,
+	category: &'a str,
+	workforce: i64,
+}
+
+pub fn get_filter() -> Value {
+	return json!({
+		"pop": { "*": {
+			"species": true,
+			"species_index": true,
+			"planet": true,
+			"ethos": true,
+			"category": true,
+			"job": true,
+		} },
+		"pop_groups": { "*": {
+			"key": true,
+			"planet": true,
+			"size": true,
+		} },
+		"pop_jobs": { "*": {
+			"type": true,
+			"planet": true,
+			"workforce": true,
+		} },
+		"species_db": { "*": { "name": true, "class": true } },
+		"galactic_object": { "*": { "planet": true } },
+		"planets": { "planet": { "*": { "owner": true, "coordinate": true } } },
+	});
+}
+
+/// Job categories by job type (eg "miner" to "worker"), from common/pop_jobs in load order
+///
+/// Job files are full of script like `num_pops > 5` that the parser can't represent,
+/// so each job's top-level `category` is read straight from the tokens
+pub fn get_job_categories(data_fs: &DataFs) -> HashMap<String, String> {
+	let mut categories = HashMap::new();
+	for file in data_fs.get_files(
+		Path::new("common/pop_jobs"),
+		&FileFilter::Extension(OsString::from("txt")),
+		1,
+	) {
+		match file.read_to_string() {
+			Ok(content) => categories.extend(parse_job_categories(&parser::normalize_text(&content))),
+			Err(err) => println!("failed to read jobs {}: {}", file.path().display(), err),
+		}
+	}
+	return categories;
+}
+
+fn parse_job_categories(content: &str) -> Vec<(String, String)> {
+	let mut categories = vec![];
+	let mut depth = 0;
+	let mut job = None;
+	let mut key = None;
+	let mut assigning = false;
+	for token in Token::lexer(content).flatten() {
+		match token {
+			Token::Text(text) | Token::String(text) => {
+				if !assigning {
+					key = Some(text);
+					continue;
+				}
+				if let (1, Some(job), Some("category")) = (depth, job, key) {
+					categories.push((String::from(job), String::from(text)));
+				}
+				key = None;
+				assigning = false;
+			}
+			Token::Equals => assigning = key.is_some(),
+			Token::Open => {
+				if depth == 0 {
+					job = if assigning { key } else { None };
+				}
+				depth += 1;
+				key = None;
+				assigning = false;
+			}
+			Token::Close => {
+				depth = std::cmp::max(depth, 1) - 1;
+				key = None;
+				assigning = false;
+			}
+			Token::Comment => (),
+		}
+	}
+	return categories;
+}
+
+/// Jobs are counted by category, so job types missing from job_categories aren't counted
+pub fn get_demographics(
+	state: &Value,
+	job_categories: &HashMap<String, String>,
+) -> DemographicsReport {
+	let planet_systems = gamestate::get_planet_systems(state);
+	let planets: HashMap<i64, &Value> = get_db(state, &["planets", "planet"]).into_iter().collect();
+	let locate = |planet_id: &i64| {
+		let planet = planets.get(planet_id);
+		let system = planet_systems
+			.get(planet_id)
+			.copied()
+			.or_else(|| planet.and_then(|planet| gamestate::get_origin(planet.get("coordinate"))));
+		let owner = planet.and_then(|planet| get_i64(planet, "owner"));
+		return (system, owner);
+	};
+
+	let mut report = DemographicsReport {
+		species: BTreeMap::new(),
+		systems: BTreeMap::new(),
+		countries: BTreeMap::new(),
+	};
+	for pop in get_pop_counts(state, job_categories) {
+		let (system, owner) = locate(&pop.planet);
+		if let Some(system) = system {
+			report.systems.entry(system).or_default().add(&pop);
+		}
+		if let Some(owner) = owner {
+			report.countries.entry(owner).or_default().add(&pop);
+		}
+	}
+	for job in get_job_counts(state, job_categories) {
+		let (system, owner) = locate(&job.planet);
+		if let Some(system) = system {
+			report.systems.entry(system).or_default().add_job(&job);
+		}
+		if let Some(owner) = owner {
+			report.countries.entry(owner).or_default().add_job(&job);
+		}
+	}
+
+	let species_db: HashMap<i64, &Value> = get_db(state, &["species_db"]).into_iter().collect();
+	for demographics in report.systems.values().chain(report.countries.values()) {
+		for species in demographics.species.keys() {
+			report.species.entry(*species).or_insert_with(|| {
+				let species = species_db.get(species);
+				SpeciesSummary {
+					name: species.and_then(|species| get_name(species)),
+					class: species
+						.and_then(|species| get_str(species, "class"))
+						.map(String::from),
+				}
+			});
+		}
+	}
+	return report;
+}
+
+fn get_pop_counts<'a>(
+	state: &'a Value,
+	job_categories: &'a HashMap<String, String>,
+) -> Vec<PopCount<'a>> {
+	let mut pops = vec![];
+	for (_, pop) in get_db(state, &["pop"]) {
+		let planet = match get_i64(pop, "planet") {
+			Some(planet) => planet,
+			None => continue,
+		};
+		pops.push(PopCount {
+			planet,
+			size: 1,
+			species: get_i64(pop, "species").or_else(|| get_i64(pop, "species_index")),
+			ethic: pop
+				.get("ethos")
+				.and_then(|ethos| parser::get_all(ethos, "ethic").into_iter().next())
+				.and_then(Value::as_str),
+			stratum: get_str(pop, "category"),
+			// a pop's stratum comes from its job, so it stands in for modded jobs we don't know
+			job_category: get_str(pop, "job").and_then(|job| {
+				job_categories
+					.get(job)
+					.map(String::as_str)
+					.or_else(|| get_str(pop, "category"))
+			}),
+		});
+	}
+	for (_, group) in get_db(state, &["pop_groups"]) {
+		let planet = match get_i64(group, "planet") {
+			Some(planet) => planet,
+			None => continue,
+		};
+		let key = group.get("key");
+		pops.push(PopCount {
+			planet,
+			size: get_i64(group, "size").unwrap_or(0),
+			species: key.and_then(|key| get_i64(key, "species")),
+			ethic: key.and_then(|key| get_str(key, "ethic")),
+			stratum: key.and_then(|key| get_str(key, "category")),
+			job_category: None,
+		});
+	}
+	return pops;
+}
+
+/// From 4.0, pop groups don't record jobs; instead each job type on a planet records its workforce
+fn get_job_counts<'a>(
+	state: &'a Value,
+	job_categories: &'a HashMap<String, String>,
+) -> Vec<JobCount<'a>> {
+	return get_db(state, &["pop_jobs"])
+		.into_iter()
+		.filter_map(|(_, job)| {
+			Some(JobCount {
+				planet: get_i64(job, "planet")?,
+				category: job_categories.get(get_str(job, "type")?)?,
+				workforce: get_f64(job, "workforce")?.round() as i64,
+			})
+		})
+		.filter(|job| job.workforce > 0)
+		.collect();
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::gamestate::tests::{parse_save, sample_state};
+
+	fn job_categories() -> HashMap<String, String> {
+		return HashMap::from([
+			(String::from("miner"), String::from("worker")),
+			(String::from("researcher"), String::from("specialist")),
+		]);
+	}
+
+	#[test]
+	fn test_get_demographics() {
+		let mut state = sample_state();
+		let pops = parse_save(
+			"pop={
+				1={ species=0 planet=101 ethos={ ethic=ethic_egalitarian } category=specialist job=researcher }
+				2={ species_index=0 planet=101 ethos={ ethic=ethic_xenophile } category=worker job=miner }
+				3=none
+			}
+			pop_groups={
+				1={ key={ species=1 category=worker ethic=ethic_militarist } planet=102 size=250 }
+				2={ key={ species=0 category=worker } planet=102 size=50 }
+			}
+			pop_jobs={
+				1={ type=miner planet=102 workforce=200 }
+				2={ type=researcher planet=102 workforce=50.4 }
+				3={ type=modded_job planet=102 workforce=10 }
+				4={ type=miner planet=102 workforce=0 }
+			}",
+		);
+		for (key, value) in pops.as_object().unwrap() {
+			state[key] = value.clone();
+		}
+
+		let report = get_demographics(&state, &job_categories());
+		assert_eq!(report.species.len(), 2);
+		assert_eq!(report.species[&1].name.as_deref(), Some("Sirian"));
+
+		let earth = &report.countries[&0];
+		assert_eq!(earth.total, 2);
+		assert_eq!(earth.species, BTreeMap::from([(0, 2)]));
+		assert_eq!(
+			earth.job_categories,
+			BTreeMap::from([(String::from("specialist"), 1), (String::from("worker"), 1)])
+		);
+		assert_eq!(report.systems[&0], *earth);
+
+		let sirius = &report.systems[&2];
+		assert_eq!(sirius.total, 300);
+		assert_eq!(sirius.species, BTreeMap::from([(0, 50), (1, 250)]));
+		assert_eq!(
+			sirius.ethics,
+			BTreeMap::from([(String::from("ethic_militarist"), 250)])
+		);
+		assert_eq!(sirius.strata[&String::from("worker")], 300);
+		assert_eq!(
+			sirius.job_categories,
+			BTreeMap::from([
+				(String::from("specialist"), 50),
+				(String::from("worker"), 200)
+			])
+		);
+	}
+
+	#[test]
+	fn test_planet_without_system() {
+		// a planet missing from every system's planet list and without a coordinate origin
+		let state = parse_save(
+			"planets={ planet={ 5={ owner=3 } } }
+			pop_groups={
+				1={ key={ species=0 category=worker } planet=5 size=100 }
+				2={ key={ species=0 category=worker } planet=6 size=40 }
+			}
+			pop_jobs={ 1={ type=miner planet=5 workforce=80 } }",
+		);
+		let report = get_demographics(&state, &job_categories());
+		assert!(report.systems.is_empty());
+		assert_eq!(report.species.keys().collect::<Vec<&i64>>(), vec![&0]);
+		let country = &report.countries[&3];
+		assert_eq!(country.total, 100);
+		assert_eq!(country.job_categories[&String::from("worker")], 80);
+		assert_eq!(report.countries.len(), 1);
+	}
+
+	#[test]
+	fn test_parse_job_categories() {
+		let categories = parse_job_categories(
+			r#"
+			@base_output = 4
+			miner = {
+				category = worker
+				possible = { planet = { num_pops > 5 } category = ignored }
+				resources = { category = planet_jobs produces = { minerals = @base_output } }
+			}
+			# comment = { category = no }
+			"researcher" = { icon = researcher category = "specialist" }
+			job_without_category = { icon = x }
+			"#,
+		);
+		assert_eq!(
+			categories,
+			vec![
+				(String::from("miner"), String::from("worker")),
+				(String::from("researcher"), String::from("specialist")),
+			]
+		);
+	}
+}
@@ -0,0 +1,378 @@
+use crate::gamestate::{get_db, get_f64, get_i64, get_i64_list, get_list, get_name, get_str};
+use crate::parser;
+use serde_json::{json, Value};
+use std::collections::HashSet;
+
+#[derive(serde::Serialize, PartialEq, Debug)]
+pub struct Diplomacy {
+	pub wars: Vec<War>,
+	pub federations: Vec<Federation>,
+	pub subjects: Vec<Subject>,
+	pub relations: Vec<Relation>,
+}
+
+#[derive(serde::Serialize, PartialEq, Debug)]
+pub struct War {
+	pub war_id: i64,
+	pub name: Option<String>,
+	pub start_date: Option<String>,
+	pub attackers: Vec<i64>,
+	pub defenders: Vec<i64>,
+	/// The war goal type, eg "wg_conquest"
+	pub attacker_war_goal: Option<String>,
+	pub defender_war_goal: Option<String>,
+	/// From 0 to 1, where 1 forces a status quo peace
+	pub attacker_war_exhaustion: Option<f64>,
+	pub defender_war_exhaustion: Option<f64>,
+	pub battles: Vec<Battle>,
+}
+
+#[derive(serde::Serialize, PartialEq, Debug)]
+pub struct Battle {
+	pub date: Option<String>,
+	pub system_id: Option<i64>,
+	pub planet_id: Option<i64>,
+	/// Eg "ships" for space battles or "armies" for invasions
+	#[serde(rename = "type")]
+	pub battle_type: Option<String>,
+	pub attackers: Vec<i64>,
+	pub defenders: Vec<i64>,
+	pub attacker_victory: Option<bool>,
+	pub attacker_losses: Option<f64>,
+	pub defender_losses: Option<f64>,
+}
+
+#[derive(serde::Serialize, PartialEq, Debug)]
+pub struct Federation {
+	pub federation_id: i64,
+	pub name: Option<String>,
+	/// Eg "default_federation" or "trade_federation"
+	#[serde(rename = "type")]
+	pub federation_type: String,
+	pub leader: Option<i64>,
+	pub members: Vec<i64>,
+	pub associates: Vec<i64>,
+}
+
+#[derive(serde::Serialize, PartialEq, Debug)]
+pub struct Subject {
+	pub overlord: i64,
+	pub subject: i64,
+	/// The agreement's status, eg "active"; None for saves from before subject agreements
+	pub status: Option<String>,
+}
+
+/// How one country sees another; each pair of countries usually has a relation in both directions
+#[derive(serde::Serialize, PartialEq, Debug)]
+pub struct Relation {
+	pub owner: i64,
+	pub country: i64,
+	pub opinion: f64,
+	pub trust: Option<f64>,
+	/// The statuses that are set, out of RELATION_STATUSES
+	pub statuses: Vec<String>,
+	/// The wars the two countries are fighting each other in
+	pub wars: Vec<i64>,
+}
+
+/// The boolean flags of a save's relations, in the order they're reported
+const RELATION_STATUSES: [&str; 12] = [
+	"contact",
+	"communications",
+	"embassy",
+	"borders",
+	"closed_borders",
+	"migration_access",
+	"non_aggression_pact",
+	"defensive_pact",
+	"commercial_pact",
+	"research_agreement",
+	"is_rival",
+	"hostile",
+];
+
+pub fn get_filter() -> Value {
+	return json!({
+		"war": true,
+		"federation": true,
+		"agreements": true,
+		"country": { "*": {
+			"overlord": true,
+			"relations_manager": true,
+		} },
+	});
+}
+
+pub fn get_diplomacy(state: &Value) -> Diplomacy {
+	return Diplomacy {
+		wars: get_wars(state),
+		federations: get_federations(state),
+		subjects: get_subjects(state),
+		relations: get_relations(state),
+	};
+}
+
+fn get_wars(state: &Value) -> Vec<War> {
+	return get_db(state, &["war"])
+		.into_iter()
+		.map(|(id, war)| War {
+			war_id: id,
+			name: get_name(war),
+			start_date: get_str(war, "start_date").map(String::from),
+			attackers: get_participants(war, "attackers"),
+			defenders: get_participants(war, "defenders"),
+			attacker_war_goal: war.get("attacker_war_goal").and_then(get_war_goal_type),
+			defender_war_goal: war.get("defender_war_goal").and_then(get_war_goal_type),
+			attacker_war_exhaustion: get_f64(war, "attacker_war_exhaustion"),
+			defender_war_exhaustion: get_f64(war, "defender_war_exhaustion"),
+			battles: get_list(war, "battles")
+				.into_iter()
+				.map(|battle| Battle {
+					date: get_str(battle, "date").map(String::from),
+					system_id: get_i64(battle, "system"),
+					planet_id: get_i64(battle, "planet"),
+					battle_type: get_str(battle, "type").map(String::from),
+					attackers: get_i64_list(battle, "attackers"),
+					defenders: get_i64_list(battle, "defenders"),
+					attacker_victory: battle.get("attacker_victory").and_then(Value::as_bool),
+					attacker_losses: get_f64(battle, "attacker_losses"),
+					defender_losses: get_f64(battle, "defender_losses"),
+				})
+				.collect(),
+		})
+		.collect();
+}
+
+/// Participants are saved as `{ country = 1 call_type = primary ... }`
+fn get_participants(war: &Value, side: &str) -> Vec<i64> {
+	return get_list(war, side)
+		.into_iter()
+		.filter_map(|participant| get_i64(participant, "country"))
+		.collect();
+}
+
+/// Accepts both `war_goal = { type = wg_conquest ... }` and `war_goal = wg_conquest`
+fn get_war_goal_type(war_goal: &Value) -> Option<String> {
+	return match war_goal {
+		Value::String(war_goal_type) => Some(war_goal_type.clone()),
+		_ => get_str(war_goal, "type").map(String::from),
+	};
+}
+
+fn get_federations(state: &Value) -> Vec<Federation> {
+	return get_db(state, &["federation"])
+		.into_iter()
+		.map(|(id, federation)| Federation {
+			federation_id: id,
+			name: get_name(federation),
+			federation_type: federation
+				.get("federation_progress")
+				.and_then(|progress| get_str(progress, "federation_type"))
+				.unwrap_or("default_federation")
+				.to_string(),
+			leader: get_i64(federation, "leader"),
+			members: get_i64_list(federation, "members"),
+			associates: get_i64_list(federation, "associates"),
+		})
+		.collect();
+}
+
+/// From subject agreements, plus any `overlord` the agreements don't cover (eg in older saves)
+fn get_subjects(state: &Value) -> Vec<Subject> {
+	let mut subjects: Vec<Subject> = get_db(state, &["agreements", "agreements"])
+		.into_iter()
+		.filter_map(|(_, agreement)| {
+			Some(Subject {
+				overlord: get_i64(agreement, "owner")?,
+				subject: get_i64(agreement, "target")?,
+				status: get_str(agreement, "active_status").map(String::from),
+			})
+		})
+		.collect();
+	let covered: HashSet<(i64, i64)> = subjects
+		.iter()
+		.map(|subject| (subject.overlord, subject.subject))
+		.collect();
+	for (id, country) in get_db(state, &["country"]) {
+		if let Some(overlord) = get_i64(country, "overlord") {
+			if !covered.contains(&(overlord, id)) {
+				subjects.push(Subject {
+					overlord,
+					subject: id,
+					status: None,
+				});
+			}
+		}
+	}
+	return subjects;
+}
+
+fn get_relations(state: &Value) -> Vec<Relation> {
+	let mut relations = vec![];
+	for (id, country) in get_db(state, &["country"]) {
+		let relations_manager = match country.get("relations_manager") {
+			Some(relations_manager) => relations_manager,
+			None => continue,
+		};
+		for relation in parser::get_all(relations_manager, "relation")
+			.into_iter()
+			.flat_map(|relation| match relation {
+				Value::Array(relations) => relations.iter().collect(),
+				relation => vec![relation],
+			}) {
+			let other = match get_i64(relation, "country") {
+				Some(other) => other,
+				None => continue,
+			};
+			relations.push(Relation {
+				owner: get_i64(relation, "owner").unwrap_or(id),
+				country: other,
+				opinion: get_f64(relation, "relation_current").unwrap_or(0.0),
+				trust: get_f64(relation, "trust"),
+				statuses: RELATION_STATUSES
+					.iter()
+					.filter(|status| relation.get(**status).and_then(Value::as_bool) == Some(true))
+					.map(|status| status.to_string())
+					.collect(),
+				wars: get_i64_list(relation, "wars"),
+			});
+		}
+	}
+	return relations;
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::gamestate::tests::{parse_save, sample_state};
+
+	#[test]
+	fn test_get_diplomacy() {
+		let mut state = sample_state();
+		let war = parse_save(
+			"attacker_war_goal={ type=wg_independence }
+			attacker_war_exhaustion=0.25
+			battles={
+				{
+					attackers={ 1 }
+					defenders={ 0 }
+					system=2
+					date=\"2250.03.01\"
+					type=ships
+					attacker_victory=yes
+					attacker_losses=3
+					defender_losses=10
+				}
+			}",
+		);
+		for (key, value) in war.as_object().unwrap() {
+			state["war"]["0"][key] = value.clone();
+		}
+		state["country"]["0"]["relations_manager"] = parse_save(
+			"relations_manager={
+				relation={ owner=0 country=1 relation_current=-150 contact=yes hostile=yes embassy=no wars={ 0 } }
+			}",
+		)["relations_manager"]
+			.take();
+		state["agreements"] =
+			parse_save("agreements={ agreements={ 7={ owner=0 target=1 active_status=active } } }")
+				["agreements"]
+				.take();
+
+		let diplomacy = get_diplomacy(&state);
+		let war = &diplomacy.wars[0];
+		assert_eq!(
+			(war.attackers.clone(), war.defenders.clone()),
+			(vec![1], vec![0])
+		);
+		assert_eq!(war.attacker_war_goal.as_deref(), Some("wg_independence"));
+		assert_eq!(war.attacker_war_exhaustion, Some(0.25));
+		assert_eq!(war.defender_war_goal, None);
+		assert_eq!(war.battles.len(), 1);
+		assert_eq!(
+			(war.battles[0].system_id, war.battles[0].defenders.clone()),
+			(Some(2), vec![0])
+		);
+
+		assert_eq!(diplomacy.federations[0].members, vec![0]);
+		assert_eq!(
+			diplomacy.subjects,
+			vec![Subject {
+				overlord: 0,
+				subject: 1,
+				status: Some(String::from("active")),
+			}]
+		);
+		assert_eq!(
+			diplomacy.relations,
+			vec![Relation {
+				owner: 0,
+				country: 1,
+				opinion: -150.0,
+				trust: None,
+				statuses: vec![String::from("contact"), String::from("hostile")],
+				wars: vec![0],
+			}]
+		);
+	}
+
+	#[test]
+	fn test_repeated_relations_and_overlord_only_subjects() {
+		let state = parser::parse(
+			"country={
+				0={
+					relations_manager={
+						relation={ owner=0 country=1 relation_current=50 contact=yes }
+						relation={ owner=0 country=2 relation_current=-20 trust=10 }
+						relation={ owner=0 relation_current=5 }
+					}
+				}
+				1={ overlord=0 }
+				2={ overlord=0 }
+			}
+			agreements={ agreements={ 3={ owner=0 target=2 active_status=active } } }",
+			&get_filter(),
+		)
+		.unwrap();
+		let diplomacy = get_diplomacy(&state);
+
+		let relations: Vec<(i64, i64, f64, Option<f64>)> = diplomacy
+			.relations
+			.iter()
+			.map(|relation| {
+				(
+					relation.owner,
+					relation.country,
+					relation.opinion,
+					relation.trust,
+				)
+			})
+			.collect();
+		assert_eq!(
+			relations,
+			vec![(0, 1, 50.0, None), (0, 2, -20.0, Some(10.0))]
+		);
+		assert_eq!(
+			diplomacy.relations[0].statuses,
+			vec![String::from("contact")]
+		);
+
+		// country 2 is covered by its agreement, country 1 only has an overlord
+		assert_eq!(
+			diplomacy.subjects,
+			vec![
+				Subject {
+					overlord: 0,
+					subject: 2,
+					status: Some(String::from("active")),
+				},
+				Subject {
+					overlord: 0,
+					subject: 1,
+					status: None,
+				},
+			]
+		);
+		assert!(diplomacy.wars.is_empty());
+	}
+}
@@ -0,0 +1,271 @@
+use crate::gamestate::{get_db, get_name, get_str};
+use serde_json::{json, Value};
+use std::cmp::Ordering;
+use std::collections::BTreeMap;
+
+/// How many expense categories to list per resource
+const MAX_TOP_EXPENSES: usize = 5;
+
+#[derive(serde::Serialize, PartialEq, Debug)]
+pub struct EconomySnapshot {
+	pub country_id: i64,
+	pub name: Option<String>,
+	/// By resource, eg "energy" or "alloys"
+	pub stockpiles: BTreeMap<String, f64>,
+	/// Monthly, by resource, summed over all income categories
+	pub income: BTreeMap<String, f64>,
+	/// Monthly, by resource, summed over all expense categories
+	pub expenses: BTreeMap<String, f64>,
+	/// The largest expense categories (eg "ships" or "planet_buildings") of each resource, largest first
+	pub top_expenses: BTreeMap<String, Vec<ExpenseCategory>>,
+}
+
+#[derive(serde::Serialize, PartialEq, Debug)]
+pub struct ExpenseCategory {
+	pub category: String,
+	pub amount: f64,
+}
+
+/// One CSV row per country and resource
+#[derive(serde::Serialize, PartialEq, Debug)]
+pub struct EconomyRow {
+	pub date: String,
+	pub country_id: i64,
+	pub name: Option<String>,
+	pub resource: String,
+	pub stockpile: f64,
+	pub income: f64,
+	pub expenses: f64,
+	pub net: f64,
+}
+
+pub fn get_filter() -> Value {
+	return json!({
+		"date": true,
+		"country": { "*": {
+			"name": true,
+			"budget": true,
+			"modules": { "standard_economy_module": { "resources": true } },
+		} },
+	});
+}
+
+/// Countries without any stockpiles or budget (eg the many internal countries) are skipped
+pub fn get_economy(state: &Value) -> Vec<EconomySnapshot> {
+	return get_db(state, &["country"])
+		.into_iter()
+		.filter_map(|(id, country)| {
+			let stockpiles = country
+				.get("modules")
+				.and_then(|modules| modules.get("standard_economy_module"))
+				.and_then(|module| module.get("resources"))
+				.map(get_amounts)
+				.unwrap_or_default();
+			// the last month is complete, while the current one is still being accumulated
+			let budget = country.get("budget").and_then(|budget| {
+				budget
+					.get("last_month")
+					.or_else(|| budget.get("current_month"))
+			});
+			let income = get_categories(budget.and_then(|budget| budget.get("income")));
+			let expenses = get_categories(budget.and_then(|budget| budget.get("expenses")));
+			if stockpiles.is_empty() && income.is_empty() && expenses.is_empty() {
+				return None;
+			}
+			Some(EconomySnapshot {
+				country_id: id,
+				name: get_name(country),
+				stockpiles,
+				income: sum_categories(&income),
+				expenses: sum_categories(&expenses),
+				top_expenses: get_top_expenses(&expenses),
+			})
+		})
+		.collect();
+}
+
+pub fn get_economy_rows(state: &Value, snapshots: &[EconomySnapshot]) -> Vec<EconomyRow> {
+	let date = get_str(state, "date").unwrap_or_default();
+	let mut rows = vec![];
+	for snapshot in snapshots {
+		let mut resources: Vec<&String> = snapshot
+			.stockpiles
+			.keys()
+			.chain(snapshot.income.keys())
+			.chain(snapshot.expenses.keys())
+			.collect();
+		resources.sort();
+		resources.dedup();
+		for resource in resources {
+			let income = snapshot.income.get(resource).copied().unwrap_or(0.0);
+			let expenses = snapshot.expenses.get(resource).copied().unwrap_or(0.0);
+			rows.push(EconomyRow {
+				date: date.to_string(),
+				country_id: snapshot.country_id,
+				name: snapshot.name.clone(),
+				resource: resource.clone(),
+				stockpile: snapshot.stockpiles.get(resource).copied().unwrap_or(0.0),
+				income,
+				expenses,
+				net: income - expenses,
+			});
+		}
+	}
+	return rows;
+}
+
+/// A `{ energy = 10 minerals = 5 }` block; non-numeric entries are ignored
+fn get_amounts(value: &Value) -> BTreeMap<String, f64> {
+	return match value {
+		Value::Object(map) => map
+			.iter()
+			.filter_map(|(resource, amount)| Some((resource.clone(), amount.as_f64()?)))
+			.collect(),
+		_ => BTreeMap::new(),
+	};
+}
+
+/// A budget's `{ category = { resource = amount ... } ... }`, skipping empty categories
+fn get_categories(value: Option<&Value>) -> BTreeMap<String, BTreeMap<String, f64>> {
+	return match value {
+		Some(Value::Object(map)) => map
+			.iter()
+			.map(|(category, amounts)| (category.clone(), get_amounts(amounts)))
+			.filter(|(_, amounts)| !amounts.is_empty())
+			.collect(),
+		_ => BTreeMap::new(),
+	};
+}
+
+fn sum_categories(categories: &BTreeMap<String, BTreeMap<String, f64>>) -> BTreeMap<String, f64> {
+	let mut totals = BTreeMap::new();
+	for amounts in categories.values() {
+		for (resource, amount) in amounts {
+			*totals.entry(resource.clone()).or_default() += amount;
+		}
+	}
+	return totals;
+}
+
+fn get_top_expenses(
+	expenses: &BTreeMap<String, BTreeMap<String, f64>>,
+) -> BTreeMap<String, Vec<ExpenseCategory>> {
+	let mut top_expenses: BTreeMap<String, Vec<ExpenseCategory>> = BTreeMap::new();
+	for (category, amounts) in expenses {
+		for (resource, amount) in amounts {
+			if *amount > 0.0 {
+				top_expenses
+					.entry(resource.clone())
+					.or_default()
+					.push(ExpenseCategory {
+						category: category.clone(),
+						amount: *amount,
+					});
+			}
+		}
+	}
+	for categories in top_expenses.values_mut() {
+		categories.sort_by(|a, b| {
+			b.amount
+				.partial_cmp(&a.amount)
+				.unwrap_or(Ordering::Equal)
+				.then(a.category.cmp(&b.category))
+		});
+		categories.truncate(MAX_TOP_EXPENSES);
+	}
+	return top_expenses;
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::gamestate::tests::{parse_save, sample_state};
+	use crate::parser;
+
+	#[test]
+	fn test_get_economy() {
+		let mut state = sample_state();
+		let earth = parse_save(
+			"modules={ standard_economy_module={ resources={ energy=500 minerals=1200.5 } } }
+			budget={
+				current_month={ income={ planet_jobs={ energy=1 } } }
+				last_month={
+					income={
+						country_base={ energy=20 minerals=10 }
+						planet_jobs={ energy=100 minerals=90 }
+					}
+					expenses={
+						ships={ energy=30 alloys=5 }
+						planet_buildings={ energy=40 }
+						planet_districts={ energy=10 }
+						starbases={ }
+					}
+				}
+			}",
+		);
+		for (key, value) in earth.as_object().unwrap() {
+			state["country"]["0"][key] = value.clone();
+		}
+
+		let economy = get_economy(&state);
+		assert_eq!(economy.len(), 1);
+		let earth = &economy[0];
+		assert_eq!(earth.stockpiles["minerals"], 1200.5);
+		assert_eq!(
+			earth.income,
+			BTreeMap::from([
+				(String::from("energy"), 120.0),
+				(String::from("minerals"), 100.0)
+			])
+		);
+		assert_eq!(earth.expenses["energy"], 80.0);
+		let top_energy: Vec<&str> = earth.top_expenses["energy"]
+			.iter()
+			.map(|expense| expense.category.as_str())
+			.collect();
+		assert_eq!(
+			top_energy,
+			vec!["planet_buildings", "ships", "planet_districts"]
+		);
+
+		let rows = get_economy_rows(&state, &economy);
+		let resources: Vec<&str> = rows.iter().map(|row| row.resource.as_str()).collect();
+		assert_eq!(resources, vec!["alloys", "energy", "minerals"]);
+		assert_eq!(rows[0].net, -5.0);
+		assert_eq!((rows[1].date.as_str(), rows[1].net), ("2250.06.01", 40.0));
+	}
+
+	#[test]
+	fn test_current_month_fallback() {
+		// eg a save from the first month of a game, before any month has completed
+		let state = parser::parse(
+			"country={
+				0={
+					budget={
+						current_month={
+							income={ country_base={ energy=20 } }
+							expenses={ ships={ energy=5 alloys=2 } }
+						}
+					}
+				}
+				1={ budget={ current_month={ } } }
+			}",
+			&get_filter(),
+		)
+		.unwrap();
+		let economy = get_economy(&state);
+		assert_eq!(economy.len(), 1);
+		let country = &economy[0];
+		assert!(country.stockpiles.is_empty());
+		assert_eq!(country.income["energy"], 20.0);
+		assert_eq!(
+			country.expenses,
+			BTreeMap::from([(String::from("alloys"), 2.0), (String::from("energy"), 5.0)])
+		);
+
+		// without a date the rows are still written, with an empty date
+		let rows = get_economy_rows(&state, &economy);
+		assert_eq!(rows.len(), 2);
+		assert_eq!((rows[1].date.as_str(), rows[1].net), ("", 15.0));
+	}
+}
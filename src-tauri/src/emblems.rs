@@ -0,0 +1,628 @@
+use crate::vfs::{DataFile, DataFs};
+use base64::prelude::*;
+use ddsfile::Dds;
+use image::imageops::{self, FilterType};
+use image::{Rgba, RgbaImage};
+use rayon::prelude::*;
+use std::collections::HashMap;
+use std::fs;
+use std::io::Cursor;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Transparent pixels between packed emblems, so texture filtering doesn't bleed neighbours together
+const ATLAS_PADDING: u32 = 1;
+
+/// Older atlases are deleted, since each one is a full base64 PNG
+const MAX_CACHED_ATLASES: usize = 16;
+
+#[derive(serde::Serialize, serde::Deserialize, Clone, Copy, PartialEq, Debug)]
+pub struct AtlasRect {
+	pub x: u32,
+	pub y: u32,
+	pub width: u32,
+	pub height: u32,
+}
+
+#[derive(serde::Serialize, serde::Deserialize)]
+pub struct EmblemAtlas {
+	/// PNG data url
+	pub atlas: String,
+	/// Keyed by "category/file"
+	pub index: HashMap<String, AtlasRect>,
+	/// Keys of emblems that weren't found or couldn't be decoded
+	pub missing: Vec<String>,
+}
+
+#[derive(serde::Serialize)]
+pub struct EmblemCategory {
+	pub name: String,
+	pub emblems: Vec<EmblemListing>,
+}
+
+#[derive(serde::Serialize)]
+pub struct EmblemListing {
+	pub file: String,
+	/// The data dir (or zipped mod) the emblem was loaded from, after mod overrides
+	pub source: PathBuf,
+}
+
+/// Alternative renderings for map labels, which need to stay legible on busy backgrounds
+#[derive(serde::Deserialize, Clone, Copy, PartialEq, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum EmblemVariant {
+	/// White, with the emblem's alpha, so it can be tinted
+	Silhouette,
+	/// A white ring around the emblem's shape
+	Outline,
+}
+
+pub fn get_emblem_path(category: &str, file: &str) -> PathBuf {
+	return Path::new("flags").join(category).join("map").join(file);
+}
+
+pub fn get_emblem_key(category: &str, file: &str) -> String {
+	return format!("{}/{}", category, file);
+}
+
+/// Groups the files under flags/ by category, keeping only map emblems (flags/<category>/map/<file>)
+///
+/// Expects files sorted by relative path, as returned by DataFs::get_files
+pub fn list_emblems(files: Vec<DataFile>) -> Vec<EmblemCategory> {
+	let mut categories: Vec<EmblemCategory> = vec![];
+	for file in files {
+		let components: Vec<String> = file
+			.relative_path
+			.components()
+			.map(|c| c.as_os_str().to_string_lossy().to_string())
+			.collect();
+		let (category, emblem) = match components.as_slice() {
+			[flags, category, map, emblem] if flags == "flags" && map == "map" => (category, emblem),
+			_ => continue,
+		};
+		let listing = EmblemListing {
+			file: emblem.clone(),
+			source: file.root,
+		};
+		match categories.last_mut() {
+			Some(last) if &last.name == category => last.emblems.push(listing),
+			_ => categories.push(EmblemCategory {
+				name: category.clone(),
+				emblems: vec![listing],
+			}),
+		}
+	}
+	return categories;
+}
+
+/// Decodes the full-size mip level of any DDS texture
+pub fn decode_dds(file: &DataFile) -> anyhow::Result<RgbaImage> {
+	let dds = Dds::read(&mut Cursor::new(file.read()?))?;
+	return Ok(image_dds::image_from_dds(&dds, 0)?);
+}
+
+/// Decodes the smallest mip level that is at least size, then resamples it to fit in size x size
+pub fn decode_dds_at_size(file: &DataFile, size: u32) -> anyhow::Result<RgbaImage> {
+	let dds = Dds::read(&mut Cursor::new(file.read()?))?;
+	let mip_level = select_mip_level(
+		dds.get_width(),
+		dds.get_height(),
+		dds.get_num_mipmap_levels(),
+		size,
+	);
+	let img = image_dds::image_from_dds(&dds, mip_level)?;
+	return Ok(resize_to_fit(&img, size));
+}
+
+/// Downscaling from the nearest larger mip is both faster and sharper than from the full size image
+fn select_mip_level(width: u32, height: u32, mip_levels: u32, size: u32) -> u32 {
+	let longest_side = width.max(height);
+	let mut level = 0;
+	while level + 1 < mip_levels && longest_side >> (level + 1) >= size {
+		level += 1;
+	}
+	return level;
+}
+
+pub fn resize_to_fit(img: &RgbaImage, size: u32) -> RgbaImage {
+	let longest_side = img.width().max(img.height());
+	if longest_side == size || longest_side == 0 || size == 0 {
+		return img.clone();
+	}
+	let width = (img.width() as u64 * size as u64 / longest_side as u64).max(1) as u32;
+	let height = (img.height() as u64 * size as u64 / longest_side as u64).max(1) as u32;
+	return imageops::resize(img, width, height, FilterType::Lanczos3);
+}
+
+pub fn apply_variant(img: &RgbaImage, variant: EmblemVariant) -> RgbaImage {
+	match variant {
+		EmblemVariant::Silhouette => {
+			return RgbaImage::from_fn(img.width(), img.height(), |x, y| {
+				Rgba([255, 255, 255, img.get_pixel(x, y)[3]])
+			});
+		}
+		EmblemVariant::Outline => {
+			// scale with the image, so outlines look the same at every requested size
+			let radius = (img.width().max(img.height()) / 32).max(1) as i64;
+			return RgbaImage::from_fn(img.width(), img.height(), |x, y| {
+				let mut dilated = 0;
+				for dy in -radius..=radius {
+					for dx in -radius..=radius {
+						if dx * dx + dy * dy > radius * radius {
+							continue;
+						}
+						let (nx, ny) = (x as i64 + dx, y as i64 + dy);
+						if nx >= 0 && ny >= 0 && nx < img.width() as i64 && ny < img.height() as i64 {
+							dilated = dilated.max(img.get_pixel(nx as u32, ny as u32)[3]);
+						}
+					}
+				}
+				Rgba([
+					255,
+					255,
+					255,
+					dilated.saturating_sub(img.get_pixel(x, y)[3]),
+				])
+			});
+		}
+	}
+}
+
+pub fn to_png_data_url(img: &RgbaImage) -> anyhow::Result<String> {
+	let mut bytes: Vec<u8> = Vec::new();
+	img.write_to(&mut Cursor::new(&mut bytes), image::ImageOutputFormat::Png)?;
+	return Ok(format!(
+		"data:image/png;base64,{}",
+		BASE64_STANDARD.encode(bytes)
+	));
+}
+
+/// Decodes every emblem in parallel and packs them into a single PNG
+///
+/// If cache_dir is set, the atlas is reused until the requested emblems or their source files change
+pub fn get_emblem_atlas(
+	data_fs: &DataFs,
+	emblems: &[(String, String)],
+	cache_dir: Option<&Path>,
+) -> anyhow::Result<EmblemAtlas> {
+	// sorted, so the same emblems requested in any order share a cache entry
+	let mut requested: Vec<(String, &String, &String)> = emblems
+		.iter()
+		.map(|(category, file)| (get_emblem_key(category, file), category, file))
+		.collect();
+	requested.sort();
+	requested.dedup_by(|a, b| a.0 == b.0);
+	let mut keys: Vec<String> = vec![];
+	let mut files: Vec<Option<DataFile>> = vec![];
+	for (key, category, file) in requested {
+		keys.push(key);
+		files.push(data_fs.resolve(&get_emblem_path(category, file)));
+	}
+
+	let cache_dir = cache_dir.map(|dir| dir.join("emblem-atlases"));
+	let cache_path = cache_dir
+		.as_ref()
+		.map(|dir| dir.join(format!("{:016x}.json", get_cache_key(&keys, &files))));
+	if let Some(cache_path) = &cache_path {
+		if let Some(atlas) = fs::read_to_string(cache_path)
+			.ok()
+			.and_then(|content| serde_json::from_str(&content).ok())
+		{
+			// pruning goes by modified time, so a hit marks the atlas as recently used
+			if let Err(err) = fs::File::options()
+				.write(true)
+				.open(cache_path)
+				.and_then(|file| file.set_modified(SystemTime::now()))
+			{
+				println!("failed to touch cached emblem atlas: {}", err);
+			}
+			return Ok(atlas);
+		}
+	}
+
+	let decoded: Vec<Option<RgbaImage>> = files
+		.par_iter()
+		.map(|file| match file {
+			Some(file) => decode_dds(file)
+				.map_err(|err| println!("failed to decode emblem {}: {}", file.path().display(), err))
+				.ok(),
+			None => None,
+		})
+		.collect();
+
+	let mut missing = vec![];
+	let mut images: Vec<(String, RgbaImage)> = vec![];
+	for (key, img) in keys.into_iter().zip(decoded) {
+		match img {
+			Some(img) => images.push((key, img)),
+			None => missing.push(key),
+		}
+	}
+
+	let sizes: Vec<(u32, u32)> = images
+		.iter()
+		.map(|(_, img)| (img.width(), img.height()))
+		.collect();
+	let (width, height, positions) = pack_shelves(&sizes);
+	let mut atlas_img = RgbaImage::new(width.max(1), height.max(1));
+	let mut index = HashMap::new();
+	for ((key, img), (x, y)) in images.iter().zip(positions) {
+		image::imageops::replace(&mut atlas_img, img, x as i64, y as i64);
+		index.insert(
+			key.clone(),
+			AtlasRect {
+				x,
+				y,
+				width: img.width(),
+				height: img.height(),
+			},
+		);
+	}
+	let atlas = EmblemAtlas {
+		atlas: to_png_data_url(&atlas_img)?,
+		index,
+		missing,
+	};
+
+	if let Some(cache_path) = &cache_path {
+		if let Err(err) = write_cache(cache_path, &atlas) {
+			println!("failed to cache emblem atlas: {}", err);
+		}
+	}
+	if let Some(cache_dir) = &cache_dir {
+		if let Err(err) = prune_cache(cache_dir, MAX_CACHED_ATLASES) {
+			println!("failed to prune emblem atlas cache: {}", err);
+		}
+	}
+	return Ok(atlas);
+}
+
+/// Changes when the requested emblems change, or when any source file is replaced or modified
+///
+/// Hashed with FNV-1a rather than std's DefaultHasher, whose output may change between Rust releases
+fn get_cache_key(keys: &[String], files: &[Option<DataFile>]) -> u64 {
+	let mut bytes: Vec<u8> = vec![];
+	for (key, file) in keys.iter().zip(files) {
+		bytes.extend(key.as_bytes());
+		bytes.push(0);
+		if let Some(file) = file {
+			bytes.extend(file.path().to_string_lossy().as_bytes());
+			bytes.push(0);
+			if let Some(modified) = file
+				.modified()
+				.ok()
+				.and_then(|modified| modified.duration_since(UNIX_EPOCH).ok())
+			{
+				bytes.extend(modified.as_secs().to_le_bytes());
+				bytes.extend(modified.subsec_nanos().to_le_bytes());
+			}
+		}
+		bytes.push(0);
+	}
+	return fnv1a(&bytes);
+}
+
+fn fnv1a(bytes: &[u8]) -> u64 {
+	let mut hash: u64 = 0xcbf29ce484222325;
+	for byte in bytes {
+		hash ^= *byte as u64;
+		hash = hash.wrapping_mul(0x100000001b3);
+	}
+	return hash;
+}
+
+/// Deletes all but the `keep` most recently used atlases
+fn prune_cache(dir: &Path, keep: usize) -> anyhow::Result<()> {
+	let mut entries: Vec<(SystemTime, PathBuf)> = fs::read_dir(dir)?
+		.filter_map(|entry| entry.ok())
+		.map(|entry| entry.path())
+		.filter(|path| path.extension().is_some_and(|ext| ext == "json"))
+		.filter_map(|path| Some((fs::metadata(&path).ok()?.modified().ok()?, path)))
+		.collect();
+	entries.sort_by_key(|(modified, _)| std::cmp::Reverse(*modified));
+	for (_, path) in entries.into_iter().skip(keep) {
+		fs::remove_file(path)?;
+	}
+	return Ok(());
+}
+
+fn write_cache(path: &Path, atlas: &EmblemAtlas) -> anyhow::Result<()> {
+	if let Some(dir) = path.parent() {
+		fs::create_dir_all(dir)?;
+	}
+	fs::write(path, serde_json::to_string(atlas)?)?;
+	return Ok(());
+}
+
+/// Packs rects in rows ("shelves"), tallest first, aiming for a roughly square atlas
+///
+/// Returns the atlas width and height, and the position of each rect in input order
+fn pack_shelves(sizes: &[(u32, u32)]) -> (u32, u32, Vec<(u32, u32)>) {
+	let area: u64 = sizes
+		.iter()
+		.map(|(w, h)| (*w + ATLAS_PADDING) as u64 * (*h + ATLAS_PADDING) as u64)
+		.sum();
+	let max_width = sizes.iter().map(|(w, _)| *w).max().unwrap_or(0);
+	let target_width = max_width.max((area as f64).sqrt().ceil() as u32);
+
+	let mut order: Vec<usize> = (0..sizes.len()).collect();
+	order.sort_by(|a, b| sizes[*b].1.cmp(&sizes[*a].1));
+
+	let mut positions = vec![(0, 0); sizes.len()];
+	let mut width = 0;
+	let mut x = 0;
+	let mut y = 0;
+	let mut shelf_height = 0;
+	for i in order {
+		let (w, h) = sizes[i];
+		if x > 0 && x + w > target_width {
+			x = 0;
+			y += shelf_height + ATLAS_PADDING;
+			shelf_height = 0;
+		}
+		positions[i] = (x, y);
+		width = width.max(x + w);
+		shelf_height = shelf_height.max(h);
+		x += w + ATLAS_PADDING;
+	}
+	return (width, y + shelf_height, positions);
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+	use super::*;
+	use crate::vfs::{DataLayer, FileFilter};
+	use ddsfile::{AlphaMode, D3D10ResourceDimension, DxgiFormat, NewDxgiParams};
+	use std::ffi::OsString;
+	use std::time::Duration;
+	use tempfile::TempDir;
+
+	pub(crate) fn write_dds(path: &Path, img: &RgbaImage) {
+		let mut dds = Dds::new_dxgi(NewDxgiParams {
+			height: img.height(),
+			width: img.width(),
+			depth: None,
+			format: DxgiFormat::R8G8B8A8_UNorm,
+			mipmap_levels: None,
+			array_layers: None,
+			caps2: None,
+			is_cubemap: false,
+			resource_dimension: D3D10ResourceDimension::Texture2D,
+			alpha_mode: AlphaMode::Straight,
+		})
+		.unwrap();
+		dds.data = img.as_raw().clone();
+		fs::create_dir_all(path.parent().unwrap()).unwrap();
+		dds.write(&mut fs::File::create(path).unwrap()).unwrap();
+	}
+
+	#[test]
+	fn test_list_emblems() {
+		let vanilla = TempDir::new().unwrap();
+		let mod_dir = TempDir::new().unwrap();
+		for file in [
+			"flags/animal/map/a.dds",
+			"flags/animal/map/b.dds",
+			"flags/animal/a.dds",
+			"flags/backgrounds/00_solid.dds",
+			"flags/human/map/h.dds",
+		] {
+			write_dds(&vanilla.path().join(file), &RgbaImage::new(1, 1));
+		}
+		write_dds(
+			&mod_dir.path().join("flags/animal/map/b.dds"),
+			&RgbaImage::new(1, 1),
+		);
+		let data_fs = DataFs::new(vec![
+			DataLayer::new(vanilla.path().to_path_buf(), vec![]),
+			DataLayer::new(mod_dir.path().to_path_buf(), vec![]),
+		]);
+		let files = data_fs.get_files(
+			Path::new("flags"),
+			&FileFilter::Extension(OsString::from("dds")),
+			3,
+		);
+		let categories = list_emblems(files);
+		let summary: Vec<(&str, Vec<(&str, &Path)>)> = categories
+			.iter()
+			.map(|c| {
+				(
+					c.name.as_str(),
+					c.emblems
+						.iter()
+						.map(|e| (e.file.as_str(), e.source.as_path()))
+						.collect(),
+				)
+			})
+			.collect();
+		assert_eq!(
+			summary,
+			vec![
+				(
+					"animal",
+					vec![("a.dds", vanilla.path()), ("b.dds", mod_dir.path())]
+				),
+				("human", vec![("h.dds", vanilla.path())]),
+			]
+		);
+	}
+
+	#[test]
+	fn test_decode_dds_at_size() {
+		let dir = TempDir::new().unwrap();
+		let path = dir.path().join("mips.dds");
+		let mut dds = Dds::new_dxgi(NewDxgiParams {
+			height: 8,
+			width: 8,
+			depth: None,
+			format: DxgiFormat::R8G8B8A8_UNorm,
+			mipmap_levels: Some(3),
+			array_layers: None,
+			caps2: None,
+			is_cubemap: false,
+			resource_dimension: D3D10ResourceDimension::Texture2D,
+			alpha_mode: AlphaMode::Straight,
+		})
+		.unwrap();
+		// each mip level is a different shade of gray (8x8, then 4x4, then 2x2)
+		dds.data = [(0u8, 64), (64, 16), (128, 4)]
+			.iter()
+			.flat_map(|(shade, pixels)| vec![[*shade, *shade, *shade, 255]; *pixels])
+			.flatten()
+			.collect();
+		dds.write(&mut fs::File::create(&path).unwrap()).unwrap();
+		let file = DataFile {
+			root: dir.path().to_path_buf(),
+			relative_path: PathBuf::from("mips.dds"),
+			in_archive: false,
+		};
+
+		let img = decode_dds_at_size(&file, 4).unwrap();
+		assert_eq!(img.dimensions(), (4, 4));
+		assert_eq!(*img.get_pixel(0, 0), Rgba([64, 64, 64, 255]));
+		let img = decode_dds_at_size(&file, 3).unwrap();
+		assert_eq!(img.dimensions(), (3, 3));
+		let img = decode_dds_at_size(&file, 16).unwrap();
+		assert_eq!(img.dimensions(), (16, 16));
+	}
+
+	#[test]
+	fn test_select_mip_level() {
+		assert_eq!(select_mip_level(256, 256, 9, 256), 0);
+		assert_eq!(select_mip_level(256, 256, 9, 100), 1);
+		assert_eq!(select_mip_level(256, 256, 9, 64), 2);
+		assert_eq!(select_mip_level(256, 256, 1, 64), 0);
+		assert_eq!(select_mip_level(256, 128, 9, 64), 2);
+	}
+
+	#[test]
+	fn test_variants() {
+		let img = RgbaImage::from_fn(5, 5, |x, y| {
+			if x == 2 && y == 2 {
+				Rgba([255, 0, 0, 255])
+			} else {
+				Rgba([0, 0, 0, 0])
+			}
+		});
+		let silhouette = apply_variant(&img, EmblemVariant::Silhouette);
+		assert_eq!(*silhouette.get_pixel(2, 2), Rgba([255, 255, 255, 255]));
+		assert_eq!(silhouette.get_pixel(0, 0)[3], 0);
+
+		let outline = apply_variant(&img, EmblemVariant::Outline);
+		assert_eq!(outline.get_pixel(2, 2)[3], 0);
+		assert_eq!(outline.get_pixel(2, 1)[3], 255);
+		assert_eq!(outline.get_pixel(1, 1)[3], 0);
+		assert_eq!(outline.get_pixel(0, 0)[3], 0);
+	}
+
+	#[test]
+	fn test_pack_shelves() {
+		let sizes = vec![(8, 8), (16, 4), (4, 16), (8, 8), (2, 2)];
+		let (width, height, positions) = pack_shelves(&sizes);
+		let rects: Vec<(u32, u32, u32, u32)> = sizes
+			.iter()
+			.zip(&positions)
+			.map(|((w, h), (x, y))| (*x, *y, *w, *h))
+			.collect();
+		for (i, a) in rects.iter().enumerate() {
+			assert!(a.0 + a.2 <= width && a.1 + a.3 <= height);
+			for b in rects.iter().skip(i + 1) {
+				let overlaps = a.0 < b.0 + b.2 && b.0 < a.0 + a.2 && a.1 < b.1 + b.3 && b.1 < a.1 + a.3;
+				assert!(!overlaps, "{:?} overlaps {:?}", a, b);
+			}
+		}
+		assert_eq!(pack_shelves(&[]), (0, 0, vec![]));
+	}
+
+	#[test]
+	fn test_emblem_atlas_and_cache() {
+		let data_dir = TempDir::new().unwrap();
+		let cache_dir = TempDir::new().unwrap();
+		write_dds(
+			&data_dir.path().join(get_emblem_path("animal", "a.dds")),
+			&RgbaImage::new(8, 8),
+		);
+		write_dds(
+			&data_dir.path().join(get_emblem_path("animal", "b.dds")),
+			&RgbaImage::new(4, 8),
+		);
+		let data_fs = DataFs::new(vec![DataLayer::new(data_dir.path().to_path_buf(), vec![])]);
+		let request = vec![
+			(String::from("animal"), String::from("a.dds")),
+			(String::from("animal"), String::from("b.dds")),
+			(String::from("animal"), String::from("a.dds")),
+			(String::from("animal"), String::from("missing.dds")),
+		];
+
+		let atlas = get_emblem_atlas(&data_fs, &request, Some(cache_dir.path())).unwrap();
+		assert_eq!(atlas.index.len(), 2);
+		assert_eq!(atlas.index["animal/b.dds"].width, 4);
+		assert_eq!(atlas.missing, vec![String::from("animal/missing.dds")]);
+
+		let cached = fs::read_dir(cache_dir.path().join("emblem-atlases"))
+			.unwrap()
+			.count();
+		assert_eq!(cached, 1);
+		let cache_path = fs::read_dir(cache_dir.path().join("emblem-atlases"))
+			.unwrap()
+			.next()
+			.unwrap()
+			.unwrap()
+			.path();
+		set_age(&cache_path, 1000);
+		let mut reordered = request.clone();
+		reordered.reverse();
+		let atlas_again = get_emblem_atlas(&data_fs, &reordered, Some(cache_dir.path())).unwrap();
+		assert_eq!(atlas_again.index, atlas.index);
+		let cached = fs::read_dir(cache_dir.path().join("emblem-atlases"))
+			.unwrap()
+			.count();
+		assert_eq!(cached, 1);
+		// the hit counts as a use, so the atlas is no longer the oldest
+		let modified = fs::metadata(&cache_path).unwrap().modified().unwrap();
+		assert!(modified > SystemTime::now() - Duration::from_secs(100));
+	}
+
+	#[test]
+	fn test_cache_key_is_stable() {
+		// FNV-1a reference values, which must not change between builds
+		assert_eq!(fnv1a(b""), 0xcbf29ce484222325);
+		assert_eq!(fnv1a(b"a"), 0xaf63dc4c8601ec8c);
+		assert_eq!(
+			get_cache_key(&[String::from("animal/a.dds")], &[None]),
+			get_cache_key(&[String::from("animal/a.dds")], &[None])
+		);
+		assert_ne!(
+			get_cache_key(&[String::from("animal/a.dds")], &[None]),
+			get_cache_key(&[String::from("animal/b.dds")], &[None])
+		);
+	}
+
+	#[test]
+	fn test_prune_cache() {
+		let dir = TempDir::new().unwrap();
+		for i in 0..5 {
+			set_age(&dir.path().join(format!("{}.json", i)), 100 - i);
+		}
+		fs::write(dir.path().join("other.txt"), "").unwrap();
+		prune_cache(dir.path(), 2).unwrap();
+		let mut remaining: Vec<String> = fs::read_dir(dir.path())
+			.unwrap()
+			.map(|entry| entry.unwrap().file_name().to_string_lossy().to_string())
+			.collect();
+		remaining.sort();
+		assert_eq!(remaining, vec!["3.json", "4.json", "other.txt"]);
+	}
+
+	/// Creates the file if needed, last modified secs_ago
+	fn set_age(path: &Path, secs_ago: u64) {
+		fs::File::options()
+			.create(true)
+			.truncate(false)
+			.write(true)
+			.open(path)
+			.unwrap()
+			.set_modified(SystemTime::now() - Duration::from_secs(secs_ago))
+			.unwrap();
+	}
+}
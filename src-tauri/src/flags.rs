@@ -0,0 +1,282 @@
+use crate::emblems;
+use crate::parser;
+use crate::vfs::DataFs;
+use image::imageops::{self, FilterType};
+use image::{Rgba, RgbaImage};
+use regex::Regex;
+use serde_json::Value;
+use std::collections::HashMap;
+use std::path::Path;
+
+const DEFAULT_BACKGROUND_CATEGORY: &str = "backgrounds";
+const DEFAULT_BACKGROUND_FILE: &str = "00_solid.dds";
+/// Used for unset ("null") and unknown colors, like the game does
+const FALLBACK_COLOR: Rgba<u8> = Rgba([0, 0, 0, 255]);
+
+/// A country's `flag` block from the save
+#[derive(serde::Deserialize, serde::Serialize, Clone, PartialEq, Debug)]
+pub struct Flag {
+	pub icon: Option<FlagImage>,
+	pub background: Option<FlagImage>,
+	/// Color names from flags/colors.txt; only the first two are used by backgrounds
+	#[serde(default)]
+	pub colors: Vec<String>,
+}
+
+#[derive(serde::Deserialize, serde::Serialize, Clone, PartialEq, Debug)]
+pub struct FlagImage {
+	pub category: String,
+	pub file: String,
+}
+
+/// Flag colors by name, merged from every flags/colors.txt in load order
+pub fn get_flag_colors(data_fs: &DataFs) -> HashMap<String, Rgba<u8>> {
+	let mut colors = HashMap::new();
+	for file in data_fs.get_layered_files(&Path::new("flags").join("colors.txt")) {
+		match file
+			.read_to_string()
+			.and_then(|content| parse_colors(&content))
+		{
+			Ok(file_colors) => colors.extend(file_colors),
+			Err(err) => println!("failed to parse colors {}: {}", file.path().display(), err),
+		}
+	}
+	return colors;
+}
+
+fn parse_colors(content: &str) -> anyhow::Result<HashMap<String, Rgba<u8>>> {
+	// `flag = hsv { 0.5 0.8 0.8 }` mixes an assignment and a list, so rewrite it as `flag = { hsv = { ... } }`
+	let color_re = Regex::new(r"(?i)\b(hsv|rgb)\s*(\{[^{}]*\})").unwrap();
+	let content = parser::normalize_text(content);
+	let content = color_re.replace_all(&content, |caps: &regex::Captures| {
+		format!("{{ {} = {} }}", caps[1].to_lowercase(), &caps[2])
+	});
+	let parsed = parser::parse(&content, &Value::Bool(true))?;
+	let mut colors = HashMap::new();
+	if let Some(Value::Object(definitions)) = parsed.get("colors") {
+		for (name, definition) in definitions {
+			// the flag variant is tuned for flags; fall back to the map variant for sparse mod definitions
+			let color = definition
+				.get("flag")
+				.or_else(|| definition.get("map"))
+				.and_then(parse_color);
+			if let Some(color) = color {
+				colors.insert(name.clone(), color);
+			}
+		}
+	}
+	return Ok(colors);
+}
+
+fn parse_color(value: &Value) -> Option<Rgba<u8>> {
+	let channels = |key: &str| -> Option<Vec<f64>> {
+		let values: Vec<f64> = value
+			.get(key)?
+			.as_array()?
+			.iter()
+			.filter_map(Value::as_f64)
+			.collect();
+		return if values.len() >= 3 {
+			Some(values)
+		} else {
+			None
+		};
+	};
+	if let Some(rgb) = channels("rgb") {
+		let alpha = rgb.get(3).copied().unwrap_or(255.0);
+		return Some(Rgba([
+			rgb[0].round() as u8,
+			rgb[1].round() as u8,
+			rgb[2].round() as u8,
+			alpha.round() as u8,
+		]));
+	}
+	if let Some(hsv) = channels("hsv") {
+		let [r, g, b] = hsv_to_rgb(hsv[0], hsv[1], hsv[2]);
+		return Some(Rgba([r, g, b, 255]));
+	}
+	return None;
+}
+
+/// h, s and v all range from 0 to 1, as in colors.txt
+fn hsv_to_rgb(h: f64, s: f64, v: f64) -> [u8; 3] {
+	let h = (h.rem_euclid(1.0)) * 6.0;
+	let c = v * s;
+	let x = c * (1.0 - ((h % 2.0) - 1.0).abs());
+	let (r, g, b) = match h as u8 {
+		0 => (c, x, 0.0),
+		1 => (x, c, 0.0),
+		2 => (0.0, c, x),
+		3 => (0.0, x, c),
+		4 => (x, 0.0, c),
+		_ => (c, 0.0, x),
+	};
+	let m = v - c;
+	let to_u8 = |channel: f64| ((channel + m) * 255.0).round().clamp(0.0, 255.0) as u8;
+	return [to_u8(r), to_u8(g), to_u8(b)];
+}
+
+/// Renders a flag the way the game does: the background's red and green channels are masks for the
+/// first and second flag colors, and the (full size) emblem is drawn on top
+///
+/// The result is square, at the background's native size unless size is given
+pub fn render_flag(
+	data_fs: &DataFs,
+	flag: &Flag,
+	colors: &HashMap<String, Rgba<u8>>,
+	size: Option<u32>,
+) -> anyhow::Result<RgbaImage> {
+	let (background_category, background_file) = match &flag.background {
+		Some(background) => (background.category.as_str(), background.file.as_str()),
+		None => (DEFAULT_BACKGROUND_CATEGORY, DEFAULT_BACKGROUND_FILE),
+	};
+	let background_path = Path::new("flags")
+		.join(background_category)
+		.join(background_file);
+	let background = data_fs.resolve(&background_path).ok_or(anyhow::anyhow!(
+		"No data dir contained flag background {}",
+		background_path.display()
+	))?;
+	let resolve_color = |index: usize| -> Rgba<u8> {
+		return flag
+			.colors
+			.get(index)
+			.and_then(|name| colors.get(name))
+			.copied()
+			.unwrap_or(FALLBACK_COLOR);
+	};
+	let mut img = recolor_background(
+		&emblems::decode_dds(&background)?,
+		resolve_color(0),
+		resolve_color(1),
+	);
+
+	if let Some(icon) = &flag.icon {
+		let emblem_path = Path::new("flags").join(&icon.category).join(&icon.file);
+		match data_fs.resolve(&emblem_path) {
+			Some(emblem) => {
+				let mut emblem = emblems::decode_dds(&emblem)?;
+				if emblem.dimensions() != img.dimensions() {
+					emblem = imageops::resize(&emblem, img.width(), img.height(), FilterType::Lanczos3);
+				}
+				imageops::overlay(&mut img, &emblem, 0, 0);
+			}
+			None => println!("No data dir contained emblem {}", emblem_path.display()),
+		}
+	}
+
+	if let Some(size) = size {
+		if size != img.width() || size != img.height() {
+			img = imageops::resize(&img, size, size, FilterType::Lanczos3);
+		}
+	}
+	return Ok(img);
+}
+
+fn recolor_background(mask: &RgbaImage, primary: Rgba<u8>, secondary: Rgba<u8>) -> RgbaImage {
+	let mut img = RgbaImage::new(mask.width(), mask.height());
+	for (x, y, pixel) in mask.enumerate_pixels() {
+		let primary_weight = pixel[0] as f32 / 255.0;
+		let secondary_weight = pixel[1] as f32 / 255.0;
+		let channel = |i: usize| -> u8 {
+			let value = primary[i] as f32 * primary_weight + secondary[i] as f32 * secondary_weight;
+			return value.round().min(255.0) as u8;
+		};
+		img.put_pixel(x, y, Rgba([channel(0), channel(1), channel(2), pixel[3]]));
+	}
+	return img;
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::emblems::tests::write_dds;
+	use crate::vfs::DataLayer;
+	use tempfile::TempDir;
+
+	#[test]
+	fn test_parse_colors() {
+		let colors = parse_colors(
+			r#"
+				colors = {
+					red = {
+						flag = hsv { 0.0 1.0 1.0 }
+						map = hsv { 0.0 0.5 0.5 }
+						ship = hsv { 0.0 0.5 0.5 }
+					}
+					teal = {
+						flag = rgb { 0 128 128 }
+					}
+					map_only = {
+						map = HSV { 0.5 1 1 }
+					}
+					broken = {
+						flag = { 1 2 }
+					}
+				}
+			"#,
+		)
+		.unwrap();
+		assert_eq!(colors["red"], Rgba([255, 0, 0, 255]));
+		assert_eq!(colors["teal"], Rgba([0, 128, 128, 255]));
+		assert_eq!(colors["map_only"], Rgba([0, 255, 255, 255]));
+		assert!(!colors.contains_key("broken"));
+	}
+
+	#[test]
+	fn test_parse_crlf_colors() {
+		let colors = parse_colors(
+			"\u{feff}colors = {\r\n\tred = {\r\n\t\tflag = hsv { 0.0 1.0 1.0 }\r\n\t}\r\n}\r\n",
+		)
+		.unwrap();
+		assert_eq!(colors["red"], Rgba([255, 0, 0, 255]));
+	}
+
+	#[test]
+	fn test_render_flag() {
+		let data_dir = TempDir::new().unwrap();
+		// left half is the primary color, right half the secondary
+		let background = RgbaImage::from_fn(4, 4, |x, _| {
+			if x < 2 {
+				Rgba([255, 0, 0, 255])
+			} else {
+				Rgba([0, 255, 0, 255])
+			}
+		});
+		write_dds(
+			&data_dir.path().join("flags/backgrounds/00_solid.dds"),
+			&background,
+		);
+		// opaque white in the top-left corner only
+		let emblem = RgbaImage::from_fn(4, 4, |x, y| {
+			if x == 0 && y == 0 {
+				Rgba([255, 255, 255, 255])
+			} else {
+				Rgba([0, 0, 0, 0])
+			}
+		});
+		write_dds(&data_dir.path().join("flags/animal/a.dds"), &emblem);
+		let data_fs = DataFs::new(vec![DataLayer::new(data_dir.path().to_path_buf(), vec![])]);
+		let colors = HashMap::from([
+			(String::from("red"), Rgba([255, 0, 0, 255])),
+			(String::from("blue"), Rgba([0, 0, 255, 255])),
+		]);
+		let flag = Flag {
+			icon: Some(FlagImage {
+				category: String::from("animal"),
+				file: String::from("a.dds"),
+			}),
+			background: None,
+			colors: vec![String::from("red"), String::from("blue")],
+		};
+
+		let img = render_flag(&data_fs, &flag, &colors, None).unwrap();
+		assert_eq!(img.dimensions(), (4, 4));
+		assert_eq!(*img.get_pixel(0, 0), Rgba([255, 255, 255, 255]));
+		assert_eq!(*img.get_pixel(1, 3), Rgba([255, 0, 0, 255]));
+		assert_eq!(*img.get_pixel(3, 3), Rgba([0, 0, 255, 255]));
+
+		let resized = render_flag(&data_fs, &flag, &colors, Some(16)).unwrap();
+		assert_eq!(resized.dimensions(), (16, 16));
+	}
+}
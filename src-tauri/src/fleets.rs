@@ -0,0 +1,231 @@
+use crate::gamestate::{self, get_db, get_f64, get_i64_list, get_name, get_str};
+use crate::parser;
+use serde_json::{json, Value};
+use std::collections::HashMap;
+
+#[derive(serde::Serialize, PartialEq, Debug)]
+pub struct FleetMovement {
+	pub fleet_id: i64,
+	pub name: Option<String>,
+	pub owner: Option<i64>,
+	pub military_power: f64,
+	pub num_ships: usize,
+	/// The system the fleet is in, or last left if it's between systems
+	pub system: Option<SystemPoint>,
+	/// Where the fleet is ordered to go
+	pub target: Option<SystemPoint>,
+	/// The systems still ahead on the fleet's path, in order; empty for idle fleets
+	pub path: Vec<PathStep>,
+	/// The save's movement state, eg "moving" or "idle"
+	pub state: Option<String>,
+}
+
+/// A system with its galactic coordinates, as saved (the frontend inverts x for display)
+#[derive(serde::Serialize, Clone, Copy, PartialEq, Debug)]
+pub struct SystemPoint {
+	pub system_id: i64,
+	pub x: f64,
+	pub y: f64,
+}
+
+#[derive(serde::Serialize, PartialEq, Debug)]
+pub struct PathStep {
+	#[serde(flatten)]
+	pub system: SystemPoint,
+	/// How the fleet gets to this system, eg "hyperlane" or "wormhole"
+	pub ftl: Option<String>,
+}
+
+pub fn get_filter() -> Value {
+	return json!({
+		"galactic_object": { "*": { "coordinate": true } },
+		"fleet": { "*": {
+			"name": true,
+			"station": true,
+			"military_power": true,
+			"ships": true,
+			"movement_manager": true,
+		} },
+		"country": { "*": { "fleets_manager": true } },
+	});
+}
+
+/// Every fleet except starbases, which never move
+pub fn get_fleet_movements(state: &Value) -> Vec<FleetMovement> {
+	let fleet_owners = gamestate::get_fleet_owners(state);
+	let systems: HashMap<i64, SystemPoint> = get_db(state, &["galactic_object"])
+		.into_iter()
+		.filter_map(|(id, system)| {
+			let coordinate = system.get("coordinate")?;
+			Some((
+				id,
+				SystemPoint {
+					system_id: id,
+					x: get_f64(coordinate, "x")?,
+					y: get_f64(coordinate, "y")?,
+				},
+			))
+		})
+		.collect();
+	let get_system = |coordinate: Option<&Value>| {
+		gamestate::get_origin(coordinate).and_then(|id| systems.get(&id).copied())
+	};
+
+	return get_db(state, &["fleet"])
+		.into_iter()
+		.filter(|(_, fleet)| fleet.get("station").and_then(Value::as_bool) != Some(true))
+		.map(|(id, fleet)| {
+			let movement_manager = fleet.get("movement_manager");
+			let system = movement_manager.and_then(|manager| get_system(manager.get("coordinate")));
+			let target = movement_manager.and_then(|manager| {
+				get_system(manager.get("target_coordinate")).or_else(|| {
+					get_system(
+						manager
+							.get("target")
+							.and_then(|target| target.get("coordinate")),
+					)
+				})
+			});
+			let mut path: Vec<PathStep> = vec![];
+			if let Some(manager_path) = movement_manager.and_then(|manager| manager.get("path")) {
+				for node in parser::get_all(manager_path, "node") {
+					let step = match get_system(node.get("coordinate")) {
+						Some(step) => step,
+						None => continue,
+					};
+					// moves within a system (eg to the jump point) don't show on the galaxy map
+					let previous = path.last().map_or(system.map(|s| s.system_id), |step| {
+						Some(step.system.system_id)
+					});
+					if previous != Some(step.system_id) {
+						path.push(PathStep {
+							system: step,
+							ftl: get_str(node, "ftl").map(String::from),
+						});
+					}
+				}
+			}
+			FleetMovement {
+				fleet_id: id,
+				name: get_name(fleet),
+				owner: fleet_owners.get(&id).copied(),
+				military_power: get_f64(fleet, "military_power").unwrap_or(0.0),
+				num_ships: get_i64_list(fleet, "ships").len(),
+				system,
+				target,
+				path,
+				state: movement_manager
+					.and_then(|manager| get_str(manager, "state"))
+					.map(String::from),
+			}
+		})
+		.collect();
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::gamestate::tests::{parse_save, sample_state};
+
+	#[test]
+	fn test_get_fleet_movements() {
+		let mut state = sample_state();
+		state["fleet"]["503"]["movement_manager"] = parse_save(
+			"movement_manager={
+				coordinate={ x=12.5 y=-3 origin=0 }
+				target_coordinate={ x=0 y=0 origin=2 }
+				state=moving
+				path={
+					date=\"2250.06.01\"
+					node={ coordinate={ x=40 y=0 origin=0 } ftl=none }
+					node={ coordinate={ x=0 y=0 origin=1 } ftl=hyperlane }
+					node={ coordinate={ x=0 y=0 origin=2 } ftl=hyperlane }
+					node={ coordinate={ x=5 y=5 origin=2 } ftl=none }
+				}
+			}",
+		)["movement_manager"]
+			.take();
+		let movements = get_fleet_movements(&state);
+		assert_eq!(movements.len(), 1);
+		let fleet = &movements[0];
+		assert_eq!(
+			(fleet.fleet_id, fleet.owner, fleet.num_ships),
+			(503, Some(0), 2)
+		);
+		assert_eq!(
+			fleet.system,
+			Some(SystemPoint {
+				system_id: 0,
+				x: 0.0,
+				y: 0.0
+			})
+		);
+		assert_eq!(fleet.target.map(|target| target.system_id), Some(2));
+		let path: Vec<(i64, Option<&str>)> = fleet
+			.path
+			.iter()
+			.map(|step| (step.system.system_id, step.ftl.as_deref()))
+			.collect();
+		assert_eq!(path, vec![(1, Some("hyperlane")), (2, Some("hyperlane"))]);
+		assert_eq!(fleet.path[1].system.x, 30.0);
+	}
+
+	#[test]
+	fn test_fleets_between_systems_or_without_movement() {
+		let state = parser::parse(
+			"galactic_object={
+				0={ coordinate={ x=0 y=0 origin=4294967295 } }
+				1={ coordinate={ x=100 y=0 origin=4294967295 } }
+			}
+			fleet={
+				10={
+					ships={ 1 2 3 }
+					movement_manager={
+						coordinate={ x=60 y=0 origin=0 }
+						target_coordinate={ x=100 y=0 origin=1 }
+						state=moving
+						path={ date=\"2250.06.01\" node={ coordinate={ x=100 y=0 origin=1 } ftl=hyperlane } }
+					}
+				}
+				11={ ships=4 }
+				12={ movement_manager={ coordinate={ x=5 y=5 origin=4294967295 } state=idle } }
+				13={ station=yes movement_manager={ coordinate={ x=0 y=0 origin=0 } } }
+			}
+			country={ 0={ fleets_manager={ owned_fleets={ { fleet=10 } { fleet=11 } } } } }",
+			&get_filter(),
+		)
+		.unwrap();
+		let movements = get_fleet_movements(&state);
+		assert_eq!(
+			movements
+				.iter()
+				.map(|fleet| fleet.fleet_id)
+				.collect::<Vec<i64>>(),
+			vec![10, 11, 12]
+		);
+
+		// between systems, the fleet is placed at the system it left
+		let moving = &movements[0];
+		assert_eq!(moving.system.map(|system| system.system_id), Some(0));
+		assert_eq!(moving.target.map(|target| target.system_id), Some(1));
+		assert_eq!(moving.path.len(), 1);
+		assert_eq!((moving.owner, moving.num_ships), (Some(0), 3));
+
+		let no_manager = &movements[1];
+		assert_eq!(
+			(
+				no_manager.system,
+				no_manager.target,
+				no_manager.state.as_deref()
+			),
+			(None, None, None)
+		);
+		assert!(no_manager.path.is_empty());
+		assert_eq!(no_manager.num_ships, 1);
+
+		let deep_space = &movements[2];
+		assert_eq!(deep_space.system, None);
+		assert_eq!(deep_space.owner, None);
+		assert_eq!(deep_space.state.as_deref(), Some("idle"));
+	}
+}
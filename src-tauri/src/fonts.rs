@@ -0,0 +1,369 @@
+use base64::prelude::*;
+use font_kit::family_name::FamilyName;
+use font_kit::handle::Handle;
+use font_kit::properties::{Properties, Style, Weight};
+use font_kit::source::SystemSource;
+use rayon::prelude::*;
+use skera::{subset_font, Plan, SubsetFlags, DEFAULT_LAYOUT_FEATURES};
+use std::collections::HashMap;
+use std::fs;
+use std::path::PathBuf;
+use std::sync::{Mutex, OnceLock};
+use std::time::SystemTime;
+use write_fonts::read::collections::IntSet;
+use write_fonts::read::types::{GlyphId, NameId, Tag};
+use write_fonts::read::{FontRef, TableProvider};
+
+#[derive(serde::Deserialize, Clone, Copy, PartialEq, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum FontStyle {
+	Normal,
+	Italic,
+	Oblique,
+}
+
+#[derive(serde::Serialize)]
+pub struct FontData {
+	/// The family that was matched, which can differ from the requested one
+	pub family: String,
+	pub postscript_name: Option<String>,
+	/// For the CSS format() hint, "truetype" or "opentype"
+	pub format: String,
+	/// The base64 encoded font file
+	pub data: String,
+}
+
+/// How many suggestions to return when a font is missing glyphs
+const MAX_FALLBACKS: usize = 10;
+
+/// Which characters each font file (by path and font index) was found to have glyphs for
+///
+/// Finding fallbacks checks every installed font, so without this each call would read them all again
+static GLYPH_CACHE: OnceLock<Mutex<HashMap<(PathBuf, u32), CachedGlyphs>>> = OnceLock::new();
+
+struct CachedGlyphs {
+	modified: SystemTime,
+	has_glyph: HashMap<char, bool>,
+}
+
+#[derive(serde::Serialize)]
+pub struct GlyphCoverage {
+	/// The family that was matched, which can differ from the requested one
+	pub family: String,
+	/// Characters without a glyph in the font, in order of first appearance
+	pub missing: Vec<String>,
+	/// The input strings containing at least one missing character
+	pub affected: Vec<String>,
+	/// Installed families with glyphs for some of the missing characters, best first
+	pub fallbacks: Vec<FallbackFamily>,
+}
+
+#[derive(serde::Serialize, PartialEq, Debug)]
+pub struct FallbackFamily {
+	pub family: String,
+	/// How many of the missing characters this family has glyphs for
+	pub covered: usize,
+}
+
+/// Checks whether the family can render every string (eg localized country names) without tofu
+pub fn get_glyph_coverage(family: &str, strings: &[String]) -> anyhow::Result<GlyphCoverage> {
+	let source = SystemSource::new();
+	let handle =
+		source.select_best_match(&[FamilyName::Title(family.to_string())], &Properties::new())?;
+	let (bytes, font_index) = read_handle(&handle)?;
+	let font = FontRef::from_index(&bytes, font_index)?;
+	let (missing, affected) = find_missing_chars(&font, strings)?;
+
+	let mut fallbacks = vec![];
+	if !missing.is_empty() {
+		let candidates: Vec<(String, Handle)> = source
+			.all_families()?
+			.into_iter()
+			.filter_map(|candidate| {
+				let handle = source
+					.select_best_match(&[FamilyName::Title(candidate.clone())], &Properties::new())
+					.ok()?;
+				Some((candidate, handle))
+			})
+			.collect();
+		let counts: Vec<(String, usize)> = candidates
+			.into_par_iter()
+			.map(|(candidate, handle)| (candidate, count_covered_chars_cached(&handle, &missing)))
+			.collect();
+		fallbacks = rank_fallbacks(counts);
+	}
+
+	return Ok(GlyphCoverage {
+		family: get_family_name(&handle).unwrap_or(family.to_string()),
+		missing: missing.iter().map(|c| c.to_string()).collect(),
+		affected,
+		fallbacks,
+	});
+}
+
+fn read_handle(handle: &Handle) -> anyhow::Result<(Vec<u8>, u32)> {
+	return Ok(match handle {
+		Handle::Path { path, font_index } => (fs::read(path)?, *font_index),
+		Handle::Memory { bytes, font_index } => (bytes.to_vec(), *font_index),
+	});
+}
+
+fn get_family_name(handle: &Handle) -> Option<String> {
+	return handle.load().ok().map(|font| font.family_name());
+}
+
+/// Whitespace and control characters are ignored, since fonts commonly lack glyphs for them
+fn find_missing_chars(
+	font: &FontRef,
+	strings: &[String],
+) -> anyhow::Result<(Vec<char>, Vec<String>)> {
+	let cmap = font.cmap()?;
+	let mut missing: Vec<char> = vec![];
+	let mut affected = vec![];
+	for string in strings {
+		let mut is_affected = false;
+		for c in string.chars() {
+			if c.is_whitespace() || c.is_control() || cmap.map_codepoint(c).is_some() {
+				continue;
+			}
+			is_affected = true;
+			if !missing.contains(&c) {
+				missing.push(c);
+			}
+		}
+		if is_affected {
+			affected.push(string.clone());
+		}
+	}
+	return Ok((missing, affected));
+}
+
+fn count_covered_chars(font: &FontRef, chars: &[char]) -> usize {
+	return check_glyphs(font, chars)
+		.into_iter()
+		.filter(|(_, has_glyph)| *has_glyph)
+		.count();
+}
+
+fn check_glyphs(font: &FontRef, chars: &[char]) -> Vec<(char, bool)> {
+	return match font.cmap() {
+		Ok(cmap) => chars
+			.iter()
+			.map(|c| (*c, cmap.map_codepoint(*c).is_some()))
+			.collect(),
+		Err(_) => chars.iter().map(|c| (*c, false)).collect(),
+	};
+}
+
+/// Like count_covered_chars, but only reads the font file if it has changed or some chars weren't checked before
+fn count_covered_chars_cached(handle: &Handle, chars: &[char]) -> usize {
+	let (path, font_index) = match handle {
+		Handle::Path { path, font_index } => (path, *font_index),
+		Handle::Memory { bytes, font_index } => {
+			return FontRef::from_index(bytes, *font_index)
+				.map_or(0, |font| count_covered_chars(&font, chars));
+		}
+	};
+	let modified = match fs::metadata(path).and_then(|metadata| metadata.modified()) {
+		Ok(modified) => modified,
+		Err(_) => return 0,
+	};
+	let key = (path.clone(), font_index);
+	let cache = GLYPH_CACHE.get_or_init(Default::default);
+	if let Some(cached) = cache.lock().unwrap().get(&key) {
+		if cached.modified == modified && chars.iter().all(|c| cached.has_glyph.contains_key(c)) {
+			return chars.iter().filter(|c| cached.has_glyph[c]).count();
+		}
+	}
+	// read without the lock, since fonts are checked in parallel
+	let glyphs = fs::read(path)
+		.ok()
+		.and_then(|bytes| {
+			Some(check_glyphs(
+				&FontRef::from_index(&bytes, font_index).ok()?,
+				chars,
+			))
+		})
+		.unwrap_or_else(|| chars.iter().map(|c| (*c, false)).collect());
+	let covered = glyphs.iter().filter(|(_, has_glyph)| *has_glyph).count();
+	let mut cache = cache.lock().unwrap();
+	let cached = cache.entry(key).or_insert_with(|| CachedGlyphs {
+		modified,
+		has_glyph: HashMap::new(),
+	});
+	if cached.modified != modified {
+		cached.modified = modified;
+		cached.has_glyph.clear();
+	}
+	cached.has_glyph.extend(glyphs);
+	return covered;
+}
+
+fn rank_fallbacks(counts: Vec<(String, usize)>) -> Vec<FallbackFamily> {
+	let mut fallbacks: Vec<FallbackFamily> = counts
+		.into_iter()
+		.filter(|(_, covered)| *covered > 0)
+		.map(|(family, covered)| FallbackFamily { family, covered })
+		.collect();
+	fallbacks.sort_by(|a, b| b.covered.cmp(&a.covered).then(a.family.cmp(&b.family)));
+	fallbacks.truncate(MAX_FALLBACKS);
+	return fallbacks;
+}
+
+/// The installed font closest to the requested family, weight and style, for embedding with @font-face
+///
+/// If text is given, the font is subset to just the glyphs needed to render it
+pub fn get_font_data(
+	family: &str,
+	weight: Option<f32>,
+	style: Option<FontStyle>,
+	text: Option<&str>,
+) -> anyhow::Result<FontData> {
+	let mut properties = Properties::new();
+	if let Some(weight) = weight {
+		properties.weight(Weight(weight));
+	}
+	if let Some(style) = style {
+		properties.style(match style {
+			FontStyle::Normal => Style::Normal,
+			FontStyle::Italic => Style::Italic,
+			FontStyle::Oblique => Style::Oblique,
+		});
+	}
+	let handle =
+		SystemSource::new().select_best_match(&[FamilyName::Title(family.to_string())], &properties)?;
+	let font_index = match &handle {
+		Handle::Path { font_index, .. } | Handle::Memory { font_index, .. } => *font_index,
+	};
+	let font = handle.load()?;
+	let bytes = font
+		.copy_font_data()
+		.ok_or(anyhow::anyhow!("No font data available for {}", family))?;
+	let bytes = extract_font(&bytes, font_index, text)?;
+	return Ok(FontData {
+		family: font.family_name(),
+		postscript_name: font.postscript_name(),
+		format: String::from(if bytes.starts_with(b"OTTO") {
+			"opentype"
+		} else {
+			"truetype"
+		}),
+		data: BASE64_STANDARD.encode(bytes),
+	});
+}
+
+/// Subsets the font to the characters in text, or returns it unchanged if there's no text
+///
+/// Browsers can't load a face out of a collection (.ttc), so collections are always reduced to a single face
+fn extract_font(data: &[u8], index: u32, text: Option<&str>) -> anyhow::Result<Vec<u8>> {
+	let is_collection = data.starts_with(b"ttcf");
+	if text.is_none() && !is_collection {
+		return Ok(data.to_vec());
+	}
+	let font = FontRef::from_index(data, index)?;
+	let mut unicodes = IntSet::<u32>::empty();
+	match text {
+		Some(text) => unicodes.extend(text.chars().map(|c| c as u32)),
+		None => unicodes.invert(),
+	}
+	// same defaults as harfbuzz's hb-subset
+	let mut layout_scripts = IntSet::<Tag>::empty();
+	layout_scripts.invert();
+	let mut layout_features = IntSet::<Tag>::empty();
+	layout_features.extend(DEFAULT_LAYOUT_FEATURES.iter().copied());
+	let mut name_ids = IntSet::<NameId>::empty();
+	name_ids.insert_range(NameId::from(0)..=NameId::from(6));
+	let mut name_languages = IntSet::<u16>::empty();
+	name_languages.insert(0x0409);
+	let plan = Plan::new(
+		&IntSet::<GlyphId>::empty(),
+		&unicodes,
+		&font,
+		SubsetFlags::SUBSET_FLAGS_DEFAULT,
+		&IntSet::<Tag>::empty(),
+		&layout_scripts,
+		&layout_features,
+		&name_ids,
+		&name_languages,
+	);
+	return Ok(subset_font(&font, &plan)?);
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	const FONT: &[u8] = include_bytes!("../../src/static/Orbitron-VariableFont_wght.ttf");
+
+	#[test]
+	fn test_extract_font() {
+		assert_eq!(extract_font(FONT, 0, None).unwrap(), FONT);
+
+		let subset = extract_font(FONT, 0, Some("Sol")).unwrap();
+		assert!(subset.len() < FONT.len());
+		let cmap = FontRef::new(&subset).unwrap().cmap().unwrap();
+		assert!(cmap.map_codepoint('S').is_some());
+		assert!(cmap.map_codepoint('o').is_some());
+		assert!(cmap.map_codepoint('x').is_none());
+	}
+
+	#[test]
+	fn test_find_missing_chars() {
+		// Orbitron only covers Latin
+		let font = FontRef::new(FONT).unwrap();
+		let strings = vec![
+			String::from("Sol"),
+			String::from("人類 共和国"),
+			String::from("Tiyanki 人"),
+		];
+		let (missing, affected) = find_missing_chars(&font, &strings).unwrap();
+		assert_eq!(missing, vec!['人', '類', '共', '和', '国']);
+		assert_eq!(affected, strings[1..].to_vec());
+		assert_eq!(count_covered_chars(&font, &['a', '人']), 1);
+	}
+
+	#[test]
+	fn test_count_covered_chars_cached() {
+		let dir = tempfile::TempDir::new().unwrap();
+		let path = dir.path().join("font.ttf");
+		fs::write(&path, FONT).unwrap();
+		let handle = Handle::Path {
+			path: path.clone(),
+			font_index: 0,
+		};
+		assert_eq!(count_covered_chars_cached(&handle, &['a', '人']), 1);
+
+		// unchanged files aren't read again
+		let set_modified = |modified: SystemTime| {
+			fs::File::options()
+				.write(true)
+				.open(&path)
+				.unwrap()
+				.set_modified(modified)
+				.unwrap();
+		};
+		let modified = fs::metadata(&path).unwrap().modified().unwrap();
+		fs::write(&path, b"not a font").unwrap();
+		set_modified(modified);
+		assert_eq!(count_covered_chars_cached(&handle, &['人', 'a']), 1);
+		// but new chars or a new modified time are checked against the file
+		assert_eq!(count_covered_chars_cached(&handle, &['a', 'b']), 0);
+		set_modified(modified + std::time::Duration::from_secs(10));
+		assert_eq!(count_covered_chars_cached(&handle, &['a']), 0);
+	}
+
+	#[test]
+	fn test_rank_fallbacks() {
+		let fallbacks = rank_fallbacks(vec![
+			(String::from("Latin Only"), 0),
+			(String::from("Partial"), 2),
+			(String::from("Noto Sans CJK"), 5),
+			(String::from("Another Partial"), 2),
+		]);
+		let families: Vec<&str> = fallbacks.iter().map(|f| f.family.as_str()).collect();
+		assert_eq!(
+			families,
+			vec!["Noto Sans CJK", "Another Partial", "Partial"]
+		);
+	}
+}
@@ -0,0 +1,348 @@
+use crate::parser;
+use serde_json::Value;
+use std::collections::HashMap;
+
+/// The entries of an id-keyed section like `country = { 0 = { ... } 1 = none }`, sorted by id
+///
+/// Deleted objects are saved as `none` and are skipped
+pub fn get_db<'a>(state: &'a Value, path: &[&str]) -> Vec<(i64, &'a Value)> {
+	let mut section = state;
+	for key in path {
+		section = match section.get(key) {
+			Some(value) => value,
+			None => return vec![],
+		};
+	}
+	let mut entries: Vec<(i64, &Value)> = match section.as_object() {
+		Some(map) => map
+			.iter()
+			.filter(|(_, value)| value.is_object())
+			.filter_map(|(id, value)| Some((id.parse().ok()?, value)))
+			.collect(),
+		None => vec![],
+	};
+	entries.sort_by_key(|(id, _)| *id);
+	return entries;
+}
+
+pub fn get_i64(value: &Value, key: &str) -> Option<i64> {
+	return value.get(key).and_then(Value::as_i64);
+}
+
+pub fn get_f64(value: &Value, key: &str) -> Option<f64> {
+	return value.get(key).and_then(Value::as_f64);
+}
+
+pub fn get_str<'a>(value: &'a Value, key: &str) -> Option<&'a str> {
+	return value.get(key).and_then(Value::as_str);
+}
+
+/// The items of a list like `key = { 1 2 }` or `key = { { ... } { ... } }`
+///
+/// A single `key = value` or `key = { a = 1 }` block counts as a list of one, and an empty `key = { }`
+/// (which the parser can't tell from an empty object) as an empty list;
+/// a repeated `key = 1 key = 2` needs get_all_i64 or parser::get_all instead
+pub fn get_list<'a>(value: &'a Value, key: &str) -> Vec<&'a Value> {
+	return match value.get(key) {
+		Some(Value::Array(values)) => values.iter().collect(),
+		Some(Value::Object(map)) if map.is_empty() => vec![],
+		Some(value) => vec![value],
+		None => vec![],
+	};
+}
+
+pub fn get_i64_list(value: &Value, key: &str) -> Vec<i64> {
+	return get_list(value, key)
+		.into_iter()
+		.filter_map(Value::as_i64)
+		.collect();
+}
+
+/// Like get_i64_list, but also includes values from repeated keys (eg `planet = 1 planet = 2`)
+pub fn get_all_i64(value: &Value, key: &str) -> Vec<i64> {
+	return parser::get_all(value, key)
+		.into_iter()
+		.flat_map(|value| match value {
+			Value::Array(values) => values.iter().filter_map(Value::as_i64).collect(),
+			value => value.as_i64().into_iter().collect::<Vec<i64>>(),
+		})
+		.collect();
+}
+
+/// The localisation key of a `name = { key = "..." }` block, which is the literal name for custom names
+///
+/// This is what the `name` of every object in the save reports (countries, wars, fleets etc) holds
+pub fn get_name(value: &Value) -> Option<String> {
+	return value
+		.get("name")
+		.and_then(|name| name.get("key"))
+		.and_then(|key| match key {
+			Value::String(key) => Some(key.clone()),
+			Value::Number(key) => Some(key.to_string()),
+			_ => None,
+		});
+}
+
+/// The system a planet or fleet is in, from its `coordinate = { x y origin }`
+pub fn get_origin(coordinate: Option<&Value>) -> Option<i64> {
+	return coordinate.and_then(|coordinate| get_i64(coordinate, "origin"));
+}
+
+pub fn get_fleet_owners(state: &Value) -> HashMap<i64, i64> {
+	let mut fleet_owners = HashMap::new();
+	for (country_id, country) in get_db(state, &["country"]) {
+		if let Some(fleets_manager) = country.get("fleets_manager") {
+			for owned_fleet in get_list(fleets_manager, "owned_fleets") {
+				if let Some(fleet) = get_i64(owned_fleet, "fleet") {
+					fleet_owners.insert(fleet, country_id);
+				}
+			}
+		}
+	}
+	return fleet_owners;
+}
+
+/// Systems are owned by whoever owns the fleet of the system's starbase station
+pub fn get_system_owners(state: &Value) -> HashMap<i64, i64> {
+	let fleet_owners = get_fleet_owners(state);
+	let starbases = state
+		.get("starbase_mgr")
+		.and_then(|mgr| mgr.get("starbases"));
+	let ships = state.get("ships");
+	let mut system_owners = HashMap::new();
+	for (system_id, system) in get_db(state, &["galactic_object"]) {
+		let owner = get_i64_list(system, "starbases")
+			.first()
+			.and_then(|starbase| starbases?.get(starbase.to_string()))
+			.and_then(|starbase| get_i64(starbase, "station"))
+			.and_then(|station| ships?.get(station.to_string()))
+			.and_then(|ship| get_i64(ship, "fleet"))
+			.and_then(|fleet| fleet_owners.get(&fleet));
+		if let Some(owner) = owner {
+			system_owners.insert(system_id, *owner);
+		}
+	}
+	return system_owners;
+}
+
+/// Maps every planet listed in a system to that system
+pub fn get_planet_systems(state: &Value) -> HashMap<i64, i64> {
+	let mut planet_systems = HashMap::new();
+	for (system_id, system) in get_db(state, &["galactic_object"]) {
+		for planet_id in get_all_i64(system, "planet") {
+			planet_systems.insert(planet_id, system_id);
+		}
+	}
+	return planet_systems;
+}
+
+/// The system of a country's capital planet
+pub fn get_capital_system(state: &Value, country_id: i64) -> Option<i64> {
+	let capital = state
+		.get("country")
+		.and_then(|countries| countries.get(country_id.to_string()))
+		.and_then(|country| get_i64(country, "capital"))?;
+	return get_planet_systems(state)
+		.get(&capital)
+		.copied()
+		.or_else(|| {
+			let planet = state
+				.get("planets")?
+				.get("planet")?
+				.get(capital.to_string())?;
+			get_origin(planet.get("coordinate"))
+		});
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+	use super::*;
+
+	/// Parses save text with nothing filtered out, so tests see the shapes real saves produce
+	pub(crate) fn parse_save(text: &str) -> Value {
+		return parser::parse(text, &Value::Bool(true)).unwrap();
+	}
+
+	/// Two countries, three systems in a line (0 - 1 - 2), country 0 owning 0 and 1 and country 1 owning 2
+	pub(crate) fn sample_state() -> Value {
+		return parse_save(
+			r#"
+			date="2250.06.01"
+			galactic_object={
+				0={
+					name={ key="Sol" }
+					coordinate={ x=0 y=0 origin=4294967295 }
+					star_class="sc_g"
+					starbases={ 10 }
+					hyperlane={ { to=1 length=10 } }
+					planet=100
+					planet=101
+				}
+				1={
+					name={ key="Alpha Centauri" }
+					coordinate={ x=10 y=0 origin=4294967295 }
+					star_class="sc_k"
+					starbases={ 11 }
+					hyperlane={ { to=0 length=10 } { to=2 length=20 } }
+				}
+				2={
+					name={ key="Sirius" }
+					coordinate={ x=30 y=0 origin=4294967295 }
+					star_class="sc_a"
+					starbases={ 12 }
+					hyperlane={ { to=1 length=20 } }
+					planet=102
+				}
+				3=none
+			}
+			starbase_mgr={
+				starbases={
+					10={ station=1000 }
+					11={ station=1001 }
+					12={ station=1002 }
+				}
+			}
+			ships={
+				1000={ fleet=500 }
+				1001={ fleet=501 }
+				1002={ fleet=502 }
+			}
+			fleet={
+				500={
+					name={ key="Sol Starbase" }
+					station=yes
+					military_power=100
+					ships={ 1000 }
+					movement_manager={ coordinate={ x=0 y=0 origin=0 } state=idle }
+				}
+				501={
+					name={ key="AC Starbase" }
+					station=yes
+					military_power=50
+					ships={ 1001 }
+					movement_manager={ coordinate={ x=10 y=0 origin=1 } state=idle }
+				}
+				502={
+					name={ key="Sirius Starbase" }
+					station=yes
+					military_power=50
+					ships={ 1002 }
+					movement_manager={ coordinate={ x=30 y=0 origin=2 } state=idle }
+				}
+				503={
+					name={ key="Home Fleet" }
+					military_power=1200.5
+					ships={ 1003 1004 }
+					movement_manager={ coordinate={ x=0 y=0 origin=0 } state=idle }
+				}
+			}
+			country={
+				0={
+					name={ key="United Nations of Earth" }
+					type="default"
+					capital=101
+					founder_species_ref=0
+					federation=0
+					military_power=1350.5
+					economy_power=300
+					tech_power=200
+					fleets_manager={ owned_fleets={ { fleet=500 } { fleet=501 } { fleet=503 } } }
+				}
+				1={
+					name={ key="Sirian Hegemony" }
+					type="default"
+					capital=102
+					founder_species_ref=1
+					overlord=0
+					military_power=50
+					economy_power=100
+					tech_power=90
+					fleets_manager={ owned_fleets={ { fleet=502 } } }
+				}
+				2=none
+			}
+			planets={
+				planet={
+					100={ name={ key="Sol" } planet_class="pc_g_star" planet_size=30 }
+					101={
+						name={ key="Earth" }
+						planet_class="pc_continental"
+						planet_size=16
+						owner=0
+						controller=0
+						species_information={ 0={ num_pops=40 } 1={ num_pops=2 num_enslaved=2 } }
+					}
+					102={
+						name={ key="Sirius I" }
+						planet_class="pc_desert"
+						planet_size=12
+						owner=1
+						controller=1
+						species_information={ 1={ num_pops=10 } }
+					}
+				}
+			}
+			species_db={
+				0={ name={ key="Human" } class="HUM" }
+				1={ name={ key="Sirian" } class="REP" }
+			}
+			federation={
+				0={
+					name={ key="Galactic Union" }
+					leader=0
+					members={ 0 }
+					associates={ }
+					federation_progress={ federation_type="default_federation" }
+				}
+			}
+			war={
+				0={
+					name={ key="Sirian Independence War" }
+					start_date="2250.01.01"
+					attackers={ { country=1 call_type=primary } }
+					defenders={ { country=0 call_type=primary } }
+				}
+			}
+			"#,
+		);
+	}
+
+	#[test]
+	fn test_get_list() {
+		let value = parse_save("empty={ } block={ a=1 } scalar=1 list={ 1 2 } repeated=1 repeated=2");
+		assert!(get_list(&value, "empty").is_empty());
+		assert_eq!(get_list(&value, "block").len(), 1);
+		assert_eq!(get_i64_list(&value, "scalar"), vec![1]);
+		assert_eq!(get_i64_list(&value, "list"), vec![1, 2]);
+		assert_eq!(get_i64_list(&value, "repeated"), vec![1]);
+		assert_eq!(get_all_i64(&value, "repeated"), vec![1, 2]);
+		assert!(get_list(&value, "missing").is_empty());
+	}
+
+	#[test]
+	fn test_get_db_skips_deleted_entries() {
+		let state = sample_state();
+		let ids: Vec<i64> = get_db(&state, &["galactic_object"])
+			.into_iter()
+			.map(|(id, _)| id)
+			.collect();
+		assert_eq!(ids, vec![0, 1, 2]);
+		assert!(get_db(&state, &["missing"]).is_empty());
+	}
+
+	#[test]
+	fn test_ownership() {
+		let state = sample_state();
+		assert_eq!(
+			get_system_owners(&state),
+			HashMap::from([(0, 0), (1, 0), (2, 1)])
+		);
+		assert_eq!(
+			get_planet_systems(&state),
+			HashMap::from([(100, 0), (101, 0), (102, 2)])
+		);
+		assert_eq!(get_fleet_owners(&state)[&502], 1);
+		assert_eq!(get_capital_system(&state, 1), Some(2));
+		assert_eq!(get_capital_system(&state, 2), None);
+	}
+}
@@ -0,0 +1,472 @@
+use crate::gamestate::{get_db, get_f64, get_i64, get_i64_list, get_list, get_str};
+use serde_json::{json, Value};
+use std::cmp::Ordering;
+use std::collections::{BTreeMap, BTreeSet, BinaryHeap, HashMap, HashSet, VecDeque};
+
+#[derive(serde::Serialize, Clone, Copy, PartialEq, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum LinkKind {
+	Hyperlane,
+	/// Includes strange wormholes
+	Wormhole,
+	Gateway,
+	Lgate,
+	ShroudTunnel,
+}
+
+#[derive(serde::Deserialize, Clone, Copy, PartialEq, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum Metric {
+	/// Every link counts as one jump
+	Jumps,
+	/// The hyperlanes' `length`; bypasses are instant, so they count as 0
+	Length,
+}
+
+#[derive(serde::Serialize, PartialEq, Debug)]
+pub struct GraphPath {
+	/// From start to end, inclusive
+	pub systems: Vec<i64>,
+	/// How each jump is made, so bypass jumps can be drawn differently
+	pub links: Vec<LinkKind>,
+	pub jumps: usize,
+	pub length: f64,
+}
+
+#[derive(serde::Serialize, PartialEq, Debug)]
+pub struct Chokepoints {
+	/// Systems whose loss splits their component in two or more
+	pub articulation_points: Vec<i64>,
+	/// Links whose loss splits their component in two, as (lower id, higher id)
+	pub bridges: Vec<(i64, i64)>,
+}
+
+#[derive(Clone, Copy, Debug)]
+struct Link {
+	a: i64,
+	b: i64,
+	length: f64,
+	kind: LinkKind,
+}
+
+impl Link {
+	fn other(&self, system: i64) -> i64 {
+		return if self.a == system { self.b } else { self.a };
+	}
+}
+
+/// The galaxy as an undirected graph of systems, linked by hyperlanes and optionally bypasses
+pub struct Graph {
+	links: Vec<Link>,
+	/// The indices of each system's links; a BTreeMap so results don't depend on hash order
+	adjacency: BTreeMap<i64, Vec<usize>>,
+}
+
+pub fn get_filter() -> Value {
+	return json!({
+		"galactic_object": { "*": {
+			"hyperlane": true,
+			"bypasses": true,
+			"flags": true,
+			"planet": true,
+		} },
+		"bypasses": true,
+		"country": { "*": { "capital": true } },
+		"planets": { "planet": { "*": { "coordinate": true } } },
+	});
+}
+
+impl Graph {
+	pub fn from_state(state: &Value, include_bypasses: bool) -> Graph {
+		let systems = get_db(state, &["galactic_object"]);
+		let mut graph = Graph {
+			links: vec![],
+			adjacency: systems.iter().map(|(id, _)| (*id, vec![])).collect(),
+		};
+		// hyperlanes are listed on both ends
+		let mut seen = HashSet::new();
+		for (id, system) in systems.iter() {
+			for hyperlane in get_list(system, "hyperlane") {
+				if let Some(to) = get_i64(hyperlane, "to") {
+					if seen.insert((*id.min(&to), *id.max(&to))) {
+						let length = get_f64(hyperlane, "length").unwrap_or(0.0);
+						graph.add_link(*id, to, length, LinkKind::Hyperlane);
+					}
+				}
+			}
+		}
+		if include_bypasses {
+			graph.add_bypasses(state, &systems);
+		}
+		return graph;
+	}
+
+	/// Wormholes link in pairs, gateways all link to each other,
+	/// and L-gates and shroud tunnels link to their cluster's nexus system
+	fn add_bypasses(&mut self, state: &Value, systems: &[(i64, &Value)]) {
+		// inactive bypasses (eg derelict gateways and closed L-gates) can't be traveled
+		let bypasses: HashMap<i64, &Value> = get_db(state, &["bypasses"])
+			.into_iter()
+			.filter(|(_, bypass)| bypass.get("active").and_then(Value::as_bool) != Some(false))
+			.collect();
+		let mut bypass_systems: BTreeMap<i64, i64> = BTreeMap::new();
+		for (system_id, system) in systems {
+			for bypass_id in get_i64_list(system, "bypasses") {
+				if bypasses.contains_key(&bypass_id) {
+					bypass_systems.insert(bypass_id, *system_id);
+				}
+			}
+		}
+		let find_nexus = |flag: &str| {
+			systems
+				.iter()
+				.find(|(_, system)| {
+					system
+						.get("flags")
+						.and_then(|flags| flags.get(flag))
+						.is_some()
+				})
+				.map(|(id, _)| *id)
+		};
+		let lgate_nexus = find_nexus("lcluster1");
+		let shroud_tunnel_nexus = find_nexus("shroud_tunnel_nexus");
+
+		let mut gateways = vec![];
+		for (bypass_id, system_id) in bypass_systems.iter() {
+			let bypass = bypasses[bypass_id];
+			match get_str(bypass, "type") {
+				Some("wormhole") | Some("strange_wormhole") => {
+					let linked_system =
+						get_i64(bypass, "linked_to").and_then(|linked_to| bypass_systems.get(&linked_to));
+					match linked_system {
+						// each end links to the other, so only add it once
+						Some(linked_system) if system_id < linked_system => {
+							self.add_link(*system_id, *linked_system, 0.0, LinkKind::Wormhole);
+						}
+						_ => (),
+					}
+				}
+				Some("gateway") => {
+					for gateway in gateways.iter() {
+						self.add_link(*gateway, *system_id, 0.0, LinkKind::Gateway);
+					}
+					gateways.push(*system_id);
+				}
+				Some("lgate") => {
+					if let Some(nexus) = lgate_nexus {
+						self.add_link(*system_id, nexus, 0.0, LinkKind::Lgate);
+					}
+				}
+				Some("shroud_tunnel") => {
+					if let Some(nexus) = shroud_tunnel_nexus {
+						self.add_link(*system_id, nexus, 0.0, LinkKind::ShroudTunnel);
+					}
+				}
+				_ => (),
+			}
+		}
+	}
+
+	/// The graph of just the given systems and the links between them (eg a country's territory)
+	pub fn get_subgraph(&self, systems: &HashSet<i64>) -> Graph {
+		let mut subgraph = Graph {
+			links: vec![],
+			adjacency: self
+				.adjacency
+				.keys()
+				.filter(|system| systems.contains(system))
+				.map(|system| (*system, vec![]))
+				.collect(),
+		};
+		for link in self.links.iter() {
+			subgraph.add_link(link.a, link.b, link.length, link.kind);
+		}
+		return subgraph;
+	}
+
+	/// Links to unknown (eg deleted) systems and to the system itself are ignored
+	fn add_link(&mut self, a: i64, b: i64, length: f64, kind: LinkKind) {
+		if a == b || !self.adjacency.contains_key(&a) || !self.adjacency.contains_key(&b) {
+			return;
+		}
+		let index = self.links.len();
+		self.links.push(Link { a, b, length, kind });
+		self.adjacency.get_mut(&a).unwrap().push(index);
+		self.adjacency.get_mut(&b).unwrap().push(index);
+	}
+
+	pub fn get_neighbors(&self, system: i64) -> BTreeSet<i64> {
+		return self
+			.get_links(system)
+			.map(|link| link.other(system))
+			.collect();
+	}
+
+	fn get_links(&self, system: i64) -> impl Iterator<Item = &Link> + '_ {
+		return self
+			.adjacency
+			.get(&system)
+			.into_iter()
+			.flatten()
+			.map(move |index| &self.links[*index]);
+	}
+
+	fn get_cost(link: &Link, metric: Metric) -> f64 {
+		return match metric {
+			Metric::Jumps => 1.0,
+			Metric::Length => link.length,
+		};
+	}
+
+	/// Dijkstra from all sources at once, returning each reached system's distance and the link it was reached by
+	fn search(
+		&self,
+		sources: &[i64],
+		metric: Metric,
+		target: Option<i64>,
+	) -> (HashMap<i64, f64>, HashMap<i64, usize>) {
+		let mut distances: HashMap<i64, f64> = HashMap::new();
+		let mut previous: HashMap<i64, usize> = HashMap::new();
+		let mut queue = BinaryHeap::new();
+		for source in sources {
+			if self.adjacency.contains_key(source) {
+				distances.insert(*source, 0.0);
+				queue.push(QueueEntry(0.0, *source));
+			}
+		}
+		while let Some(QueueEntry(distance, system)) = queue.pop() {
+			if distance > distances[&system] {
+				continue;
+			}
+			if Some(system) == target {
+				break;
+			}
+			for index in self.adjacency[&system].iter() {
+				let link = &self.links[*index];
+				let next = link.other(system);
+				let next_distance = distance + Graph::get_cost(link, metric);
+				if distances.get(&next).map_or(true, |d| next_distance < *d) {
+					distances.insert(next, next_distance);
+					previous.insert(next, *index);
+					queue.push(QueueEntry(next_distance, next));
+				}
+			}
+		}
+		return (distances, previous);
+	}
+
+	/// The distance of every reachable system from the nearest source (eg from a capital, for rings)
+	pub fn get_distances(&self, sources: &[i64], metric: Metric) -> HashMap<i64, f64> {
+		return self.search(sources, metric, None).0;
+	}
+
+	pub fn get_shortest_path(&self, from: i64, to: i64, metric: Metric) -> Option<GraphPath> {
+		let (distances, previous) = self.search(&[from], metric, Some(to));
+		distances.get(&to)?;
+		let mut systems = vec![to];
+		let mut links = vec![];
+		let mut length = 0.0;
+		let mut system = to;
+		while system != from {
+			let link = &self.links[previous[&system]];
+			length += link.length;
+			links.push(link.kind);
+			system = link.other(system);
+			systems.push(system);
+		}
+		systems.reverse();
+		links.reverse();
+		return Some(GraphPath {
+			jumps: links.len(),
+			systems,
+			links,
+			length,
+		});
+	}
+
+	/// Groups of systems reachable from each other, largest first
+	pub fn get_components(&self) -> Vec<Vec<i64>> {
+		let mut visited = HashSet::new();
+		let mut components = vec![];
+		for start in self.adjacency.keys() {
+			if !visited.insert(*start) {
+				continue;
+			}
+			let mut component = vec![];
+			let mut queue = VecDeque::from([*start]);
+			while let Some(system) = queue.pop_front() {
+				component.push(system);
+				for next in self.get_neighbors(system) {
+					if visited.insert(next) {
+						queue.push_back(next);
+					}
+				}
+			}
+			component.sort();
+			components.push(component);
+		}
+		components.sort_by(|a, b| b.len().cmp(&a.len()).then(a[0].cmp(&b[0])));
+		return components;
+	}
+
+	/// Articulation points and bridges, with Tarjan's algorithm
+	///
+	/// Iterative rather than recursive, since long chains of systems would overflow the stack
+	pub fn get_chokepoints(&self) -> Chokepoints {
+		let mut order: HashMap<i64, usize> = HashMap::new();
+		let mut low: HashMap<i64, usize> = HashMap::new();
+		let mut articulation_points = BTreeSet::new();
+		let mut bridges = BTreeSet::new();
+		for root in self.adjacency.keys() {
+			if order.contains_key(root) {
+				continue;
+			}
+			order.insert(*root, order.len());
+			low.insert(*root, order[root]);
+			let mut root_children = 0;
+			// (system, the link it was reached by, the position in its links to continue from)
+			let mut stack: Vec<(i64, Option<usize>, usize)> = vec![(*root, None, 0)];
+			while let Some((system, parent_link, position)) = stack.last_mut() {
+				let system = *system;
+				let links = &self.adjacency[&system];
+				if *position < links.len() {
+					let index = links[*position];
+					*position += 1;
+					// skipping the link itself rather than the parent system handles parallel links
+					if Some(index) == *parent_link {
+						continue;
+					}
+					let next = self.links[index].other(system);
+					match order.get(&next) {
+						Some(next_order) => {
+							let next_order = *next_order;
+							let system_low = low.get_mut(&system).unwrap();
+							*system_low = (*system_low).min(next_order);
+						}
+						None => {
+							order.insert(next, order.len());
+							low.insert(next, order[&next]);
+							stack.push((next, Some(index), 0));
+						}
+					}
+				} else {
+					stack.pop();
+					if let Some((parent, _, _)) = stack.last() {
+						let parent = *parent;
+						let system_low = low[&system];
+						let parent_low = low.get_mut(&parent).unwrap();
+						*parent_low = (*parent_low).min(system_low);
+						if system_low > order[&parent] {
+							bridges.insert((parent.min(system), parent.max(system)));
+						}
+						if parent == *root {
+							root_children += 1;
+						} else if system_low >= order[&parent] {
+							articulation_points.insert(parent);
+						}
+					}
+				}
+			}
+			if root_children > 1 {
+				articulation_points.insert(*root);
+			}
+		}
+		return Chokepoints {
+			articulation_points: articulation_points.into_iter().collect(),
+			bridges: bridges.into_iter().collect(),
+		};
+	}
+}
+
+/// A min-heap entry for Dijkstra, ordered by distance and then system id
+#[derive(PartialEq)]
+struct QueueEntry(f64, i64);
+
+impl Eq for QueueEntry {}
+
+impl Ord for QueueEntry {
+	fn cmp(&self, other: &Self) -> Ordering {
+		return other
+			.0
+			.partial_cmp(&self.0)
+			.unwrap_or(Ordering::Equal)
+			.then(other.1.cmp(&self.1));
+	}
+}
+
+impl PartialOrd for QueueEntry {
+	fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+		return Some(self.cmp(other));
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::gamestate::tests::{parse_save, sample_state};
+
+	/// A triangle (0, 1, 2) with a long direct lane from 0 to 2, a tail (2 - 3 - 4),
+	/// and a separate pair (5 - 6) connected to the tail by a wormhole
+	fn graph_state() -> Value {
+		return parse_save(
+			"galactic_object={
+				0={ hyperlane={ { to=1 length=10 } { to=2 length=50 } } }
+				1={ hyperlane={ { to=0 length=10 } { to=2 length=10 } } }
+				2={ hyperlane={ { to=0 length=50 } { to=1 length=10 } { to=3 length=10 } } }
+				3={ hyperlane={ { to=2 length=10 } { to=4 length=10 } } }
+				4={ hyperlane={ { to=3 length=10 } } bypasses={ 100 } }
+				5={ hyperlane={ { to=6 length=10 } } bypasses={ 101 } }
+				6={ hyperlane={ { to=5 length=10 } } bypasses={ 102 } }
+				7=none
+			}
+			bypasses={
+				100={ type=wormhole active=yes linked_to=101 }
+				101={ type=wormhole active=yes linked_to=100 }
+				102={ type=gateway active=no }
+			}",
+		);
+	}
+
+	#[test]
+	fn test_shortest_path() {
+		let graph = Graph::from_state(&graph_state(), false);
+		let by_jumps = graph.get_shortest_path(0, 3, Metric::Jumps).unwrap();
+		assert_eq!((by_jumps.systems, by_jumps.length), (vec![0, 2, 3], 60.0));
+		let by_length = graph.get_shortest_path(0, 3, Metric::Length).unwrap();
+		assert_eq!((by_length.systems, by_length.jumps), (vec![0, 1, 2, 3], 3));
+		assert_eq!(graph.get_shortest_path(0, 5, Metric::Jumps), None);
+		assert_eq!(graph.get_shortest_path(0, 7, Metric::Jumps), None);
+
+		let graph = Graph::from_state(&graph_state(), true);
+		let path = graph.get_shortest_path(0, 6, Metric::Jumps).unwrap();
+		assert_eq!(path.systems, vec![0, 2, 3, 4, 5, 6]);
+		assert_eq!(path.links[3], LinkKind::Wormhole);
+		assert_eq!(graph.get_distances(&[0], Metric::Jumps)[&6], 5.0);
+	}
+
+	#[test]
+	fn test_components_and_chokepoints() {
+		let graph = Graph::from_state(&graph_state(), false);
+		assert_eq!(
+			graph.get_components(),
+			vec![vec![0, 1, 2, 3, 4], vec![5, 6]]
+		);
+		assert_eq!(
+			graph.get_chokepoints(),
+			Chokepoints {
+				articulation_points: vec![2, 3],
+				bridges: vec![(2, 3), (3, 4), (5, 6)],
+			}
+		);
+
+		let graph = Graph::from_state(&graph_state(), true);
+		assert_eq!(graph.get_components().len(), 1);
+		assert_eq!(
+			graph.get_chokepoints().articulation_points,
+			vec![2, 3, 4, 5]
+		);
+
+		let graph = Graph::from_state(&sample_state(), false);
+		assert_eq!(graph.get_chokepoints().articulation_points, vec![1]);
+	}
+}
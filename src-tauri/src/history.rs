@@ -0,0 +1,182 @@
+use crate::campaign;
+use crate::flags::Flag;
+use crate::gamestate::get_db;
+use crate::stats::{self, CountryStats};
+use serde_json::Value;
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use std::path::PathBuf;
+
+/// Per-country time series for charting a campaign
+///
+/// Every series has one entry per date, null where the country didn't exist (yet or anymore)
+#[derive(serde::Serialize, Debug)]
+pub struct CampaignHistory {
+	pub dates: Vec<String>,
+	pub countries: Vec<CountryHistory>,
+}
+
+#[derive(serde::Serialize, Debug)]
+pub struct CountryHistory {
+	pub country_id: i64,
+	/// The latest name, since names can change (eg when a country reforms)
+	pub name: Option<String>,
+	pub flag: Option<Flag>,
+	pub systems: Vec<Option<usize>>,
+	pub pops: Vec<Option<i64>>,
+	pub military_power: Vec<Option<f64>>,
+	/// 1 for the strongest country at that date, by the sum of military, economy and tech power
+	pub rank: Vec<Option<usize>>,
+}
+
+/// The country stats and flags of one save
+struct Snapshot {
+	date: String,
+	stats: Vec<CountryStats>,
+	flags: HashMap<i64, Flag>,
+}
+
+pub fn get_campaign_history(dir: &PathBuf) -> anyhow::Result<CampaignHistory> {
+	let mut filter = stats::get_filter();
+	filter["country"]["*"]["flag"] = Value::Bool(true);
+	let snapshots = campaign::map_campaign_saves(dir, &filter, |state| {
+		let stats = stats::get_country_stats(state);
+		Snapshot {
+			date: stats
+				.first()
+				.map(|row| row.date.clone())
+				.unwrap_or_default(),
+			stats,
+			flags: get_flags(state),
+		}
+	})?
+	.into_iter()
+	.map(|(save, mut snapshot)| {
+		if snapshot.date.is_empty() {
+			snapshot.date = save.date;
+		}
+		snapshot
+	})
+	.collect();
+	return Ok(build_history(snapshots));
+}
+
+fn get_flags(state: &Value) -> HashMap<i64, Flag> {
+	return get_db(state, &["country"])
+		.into_iter()
+		.filter_map(|(id, country)| {
+			let flag = serde_json::from_value(country.get("flag")?.clone()).ok()?;
+			Some((id, flag))
+		})
+		.collect();
+}
+
+/// Country ids are reused after a country is destroyed, so a series only continues while
+/// both the id and the flag match; names aren't compared, since they change more often
+fn build_history(snapshots: Vec<Snapshot>) -> CampaignHistory {
+	let num_dates = snapshots.len();
+	let mut countries: Vec<CountryHistory> = vec![];
+	// the series each country id currently belongs to
+	let mut current: HashMap<i64, usize> = HashMap::new();
+	let mut dates = vec![];
+	for (date_index, snapshot) in snapshots.into_iter().enumerate() {
+		let ranks = get_ranks(&snapshot.stats);
+		for row in snapshot.stats {
+			let flag = snapshot.flags.get(&row.country_id).cloned();
+			let series_index = match current.get(&row.country_id) {
+				Some(index) if countries[*index].flag == flag => *index,
+				_ => {
+					countries.push(CountryHistory {
+						country_id: row.country_id,
+						name: None,
+						flag,
+						systems: vec![None; num_dates],
+						pops: vec![None; num_dates],
+						military_power: vec![None; num_dates],
+						rank: vec![None; num_dates],
+					});
+					current.insert(row.country_id, countries.len() - 1);
+					countries.len() - 1
+				}
+			};
+			let series = &mut countries[series_index];
+			if row.name.is_some() {
+				series.name = row.name;
+			}
+			series.systems[date_index] = Some(row.systems);
+			series.pops[date_index] = Some(row.pops);
+			series.military_power[date_index] = row.military_power;
+			series.rank[date_index] = ranks.get(&row.country_id).copied();
+		}
+		dates.push(snapshot.date);
+	}
+	return CampaignHistory { dates, countries };
+}
+
+fn get_ranks(stats: &[CountryStats]) -> HashMap<i64, usize> {
+	let mut powers: Vec<(i64, f64)> = stats
+		.iter()
+		.map(|row| {
+			let power = row.military_power.unwrap_or(0.0)
+				+ row.economy_power.unwrap_or(0.0)
+				+ row.tech_power.unwrap_or(0.0);
+			(row.country_id, power)
+		})
+		.collect();
+	powers.sort_by(|a, b| {
+		b.1
+			.partial_cmp(&a.1)
+			.unwrap_or(Ordering::Equal)
+			.then(a.0.cmp(&b.0))
+	});
+	return powers
+		.into_iter()
+		.enumerate()
+		.map(|(index, (id, _))| (id, index + 1))
+		.collect();
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::gamestate::tests::sample_state;
+	use serde_json::json;
+
+	fn snapshot(state: &Value) -> Snapshot {
+		let stats = stats::get_country_stats(state);
+		return Snapshot {
+			date: stats[0].date.clone(),
+			stats,
+			flags: get_flags(state),
+		};
+	}
+
+	#[test]
+	fn test_build_history() {
+		let flag = |file: &str| json!({ "icon": { "category": "human", "file": file }, "colors": ["blue", "black"] });
+		let mut first = sample_state();
+		first["country"]["0"]["flag"] = flag("flag_human_1.dds");
+		first["country"]["1"]["flag"] = flag("flag_human_2.dds");
+		// country 0 is renamed, and country 1 is destroyed and its id reused by a new country
+		let mut second = first.clone();
+		second["date"] = json!("2260.01.01");
+		second["country"]["0"]["name"] = json!({ "key": "Terran Federation" });
+		second["country"]["1"]["flag"] = flag("flag_human_3.dds");
+		second["country"]["1"]["military_power"] = json!(5000);
+
+		let history = build_history(vec![snapshot(&first), snapshot(&second)]);
+		assert_eq!(history.dates, vec!["2250.06.01", "2260.01.01"]);
+		assert_eq!(history.countries.len(), 3);
+
+		let earth = &history.countries[0];
+		assert_eq!(earth.name.as_deref(), Some("Terran Federation"));
+		assert_eq!(earth.systems, vec![Some(2), Some(2)]);
+		assert_eq!(earth.rank, vec![Some(1), Some(2)]);
+
+		let (destroyed, successor) = (&history.countries[1], &history.countries[2]);
+		assert_eq!((destroyed.country_id, successor.country_id), (1, 1));
+		assert_eq!(destroyed.pops, vec![Some(10), None]);
+		assert_eq!(successor.pops, vec![None, Some(10)]);
+		assert_eq!(successor.rank, vec![None, Some(1)]);
+	}
+}
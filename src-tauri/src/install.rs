@@ -0,0 +1,289 @@
+use crate::config;
+use regex::Regex;
+use serde_json::Value;
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use steamlocate::SteamDir;
+
+pub const STEAM_APP_ID: u32 = 281990;
+pub const INSTALL_DIR_ENV_VAR: &str = "STELLARIS_INSTALL_DIR";
+
+#[derive(serde::Serialize)]
+pub struct InstallDirCandidate {
+	pub path: PathBuf,
+	/// How this candidate was found, eg "Steam library"
+	pub reason: String,
+	/// Whether the dir contains the game data we read
+	pub valid: bool,
+}
+
+type Discoverer = fn() -> Vec<(PathBuf, String)>;
+
+/// In priority order: explicit overrides, then launchers, then well-known paths
+const DISCOVERERS: &[Discoverer] = &[
+	from_env_var,
+	from_config,
+	from_steam,
+	from_flatpak_steam,
+	from_heroic,
+	from_lutris,
+	from_common_paths,
+];
+
+/// Every install dir found by any discoverer, in priority order, without duplicates
+pub fn get_install_dir_candidates() -> Vec<InstallDirCandidate> {
+	let mut candidates: Vec<InstallDirCandidate> = vec![];
+	for discover in DISCOVERERS {
+		for (path, reason) in discover() {
+			let canonical = fs::canonicalize(&path).unwrap_or(path.clone());
+			if candidates
+				.iter()
+				.any(|c| fs::canonicalize(&c.path).unwrap_or(c.path.clone()) == canonical)
+			{
+				continue;
+			}
+			candidates.push(InstallDirCandidate {
+				valid: is_valid_install_dir(&path),
+				path,
+				reason,
+			});
+		}
+	}
+	return candidates;
+}
+
+pub fn is_valid_install_dir(path: &Path) -> bool {
+	return path.join("flags").join("colors.txt").is_file() && path.join("localisation").is_dir();
+}
+
+fn from_env_var() -> Vec<(PathBuf, String)> {
+	return env::var_os(INSTALL_DIR_ENV_VAR)
+		.filter(|path| !path.is_empty())
+		.map(|path| {
+			(
+				PathBuf::from(path),
+				format!("{} environment variable", INSTALL_DIR_ENV_VAR),
+			)
+		})
+		.into_iter()
+		.collect();
+}
+
+fn from_config() -> Vec<(PathBuf, String)> {
+	return config::load_config()
+		.install_path
+		.map(|path| (path, String::from("config file")))
+		.into_iter()
+		.collect();
+}
+
+fn find_in_steam_dir(steam_dir: Option<SteamDir>) -> Option<PathBuf> {
+	let (app, library) = steam_dir?.find_app(STEAM_APP_ID).ok()??;
+	return Some(library.resolve_app_dir(&app));
+}
+
+fn from_steam() -> Vec<(PathBuf, String)> {
+	return find_in_steam_dir(SteamDir::locate().ok())
+		.map(|path| (path, String::from("Steam library")))
+		.into_iter()
+		.collect();
+}
+
+fn from_flatpak_steam() -> Vec<(PathBuf, String)> {
+	let flatpak_steam_dir = match dirs::home_dir() {
+		Some(home) => home.join(".var/app/com.valvesoftware.Steam/.local/share/Steam"),
+		None => return vec![],
+	};
+	return find_in_steam_dir(SteamDir::from_dir(&flatpak_steam_dir).ok())
+		.map(|path| (path, String::from("Flatpak Steam library")))
+		.into_iter()
+		.collect();
+}
+
+/// Heroic records GOG installs in gog_store/installed.json
+fn from_heroic() -> Vec<(PathBuf, String)> {
+	let mut heroic_config_dirs = vec![];
+	if let Some(config_dir) = dirs::config_dir() {
+		heroic_config_dirs.push(config_dir.join("heroic"));
+	}
+	if let Some(home) = dirs::home_dir() {
+		heroic_config_dirs.push(home.join(".var/app/com.heroicgameslauncher.hgl/config/heroic"));
+	}
+	return heroic_config_dirs
+		.into_iter()
+		.map(|dir| dir.join("gog_store").join("installed.json"))
+		.filter_map(|path| fs::read_to_string(path).ok())
+		.flat_map(|content| parse_heroic_installed(&content))
+		.map(|path| (path, String::from("Heroic (GOG)")))
+		.collect();
+}
+
+fn parse_heroic_installed(content: &str) -> Vec<PathBuf> {
+	let installed: Value = serde_json::from_str(content).unwrap_or_default();
+	return installed
+		.get("installed")
+		.and_then(|installed| installed.as_array())
+		.into_iter()
+		.flatten()
+		.filter_map(|game| game.get("install_path").and_then(|path| path.as_str()))
+		.map(PathBuf::from)
+		.filter(|path| is_stellaris_dir_name(path))
+		.collect();
+}
+
+/// Lutris stores one yml per game, named after the game's slug
+fn from_lutris() -> Vec<(PathBuf, String)> {
+	let mut lutris_game_dirs = vec![];
+	if let Some(config_dir) = dirs::config_dir() {
+		lutris_game_dirs.push(config_dir.join("lutris").join("games"));
+	}
+	if let Some(data_dir) = dirs::data_dir() {
+		lutris_game_dirs.push(data_dir.join("lutris").join("games"));
+	}
+	return lutris_game_dirs
+		.into_iter()
+		.flat_map(|dir| fs::read_dir(dir).into_iter().flatten().flatten())
+		.map(|entry| entry.path())
+		.filter(|path| {
+			path
+				.file_name()
+				.map(|name| {
+					name
+						.to_string_lossy()
+						.to_lowercase()
+						.starts_with("stellaris")
+				})
+				.unwrap_or(false)
+		})
+		.filter_map(|path| fs::read_to_string(path).ok())
+		.filter_map(|content| parse_lutris_game(&content))
+		.map(|path| (path, String::from("Lutris")))
+		.collect();
+}
+
+fn parse_lutris_game(content: &str) -> Option<PathBuf> {
+	let exe_re = Regex::new(r#"(?m)^\s*exe:\s*['"]?(.+?)['"]?\s*$"#).unwrap();
+	let exe = exe_re
+		.captures(content)
+		.map(|c| c.extract::<1>().1[0].to_string())?;
+	return Path::new(&exe).parent().map(|dir| dir.to_path_buf());
+}
+
+fn is_stellaris_dir_name(path: &Path) -> bool {
+	return path
+		.file_name()
+		.map(|name| name.to_string_lossy().to_lowercase().contains("stellaris"))
+		.unwrap_or(false);
+}
+
+/// Default locations of stores and launchers we can't query directly; only existing dirs are returned
+fn from_common_paths() -> Vec<(PathBuf, String)> {
+	let mut paths: Vec<(PathBuf, &str)> = vec![];
+	match env::consts::OS {
+		"linux" => {
+			if let Some(home) = dirs::home_dir() {
+				paths.push((
+					home.join(".steam/steam/steamapps/common/Stellaris"),
+					"default Steam path",
+				));
+				paths.push((
+					home.join(".local/share/Steam/steamapps/common/Stellaris"),
+					"default Steam path",
+				));
+				paths.push((
+					home
+						.join(".var/app/com.valvesoftware.Steam/.local/share/Steam/steamapps/common/Stellaris"),
+					"default Flatpak Steam path",
+				));
+				paths.push((home.join("GOG Games/Stellaris"), "default GOG path"));
+				paths.push((home.join("Games/Heroic/Stellaris"), "default Heroic path"));
+				paths.push((home.join("Games/stellaris"), "default Lutris path"));
+			}
+		}
+		"macos" => {
+			if let Some(home) = dirs::home_dir() {
+				paths.push((
+					home.join("Library/Application Support/Steam/steamapps/common/Stellaris"),
+					"default Steam path",
+				));
+			}
+			paths.push((PathBuf::from("/Applications/Stellaris"), "default GOG path"));
+		}
+		"windows" => {
+			paths.push((
+				PathBuf::from("C:\\Program Files (x86)\\Steam\\steamapps\\common\\Stellaris"),
+				"default Steam path",
+			));
+			paths.push((
+				PathBuf::from("C:\\GOG Games\\Stellaris"),
+				"default GOG path",
+			));
+			paths.push((
+				PathBuf::from("C:\\Program Files (x86)\\GOG Galaxy\\Games\\Stellaris"),
+				"default GOG Galaxy path",
+			));
+			paths.push((
+				PathBuf::from("C:\\XboxGames\\Stellaris\\Content"),
+				"default Xbox app path",
+			));
+			paths.push((
+				PathBuf::from("C:\\Program Files\\ModifiableWindowsApps\\Stellaris"),
+				"default Xbox app path",
+			));
+			paths.push((
+				PathBuf::from("C:\\Program Files (x86)\\Paradox Interactive\\Stellaris"),
+				"default Paradox Store path",
+			));
+		}
+		_ => (),
+	}
+	return paths
+		.into_iter()
+		.filter(|(path, _)| path.is_dir())
+		.map(|(path, reason)| (path, reason.to_string()))
+		.collect();
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use tempfile::TempDir;
+
+	#[test]
+	fn test_is_valid_install_dir() {
+		let dir = TempDir::new().unwrap();
+		assert!(!is_valid_install_dir(dir.path()));
+		fs::create_dir_all(dir.path().join("flags")).unwrap();
+		fs::write(dir.path().join("flags").join("colors.txt"), "").unwrap();
+		assert!(!is_valid_install_dir(dir.path()));
+		fs::create_dir_all(dir.path().join("localisation")).unwrap();
+		assert!(is_valid_install_dir(dir.path()));
+	}
+
+	#[test]
+	fn test_parse_heroic_installed() {
+		let paths = parse_heroic_installed(
+			r#"{
+				"installed": [
+					{ "appName": "1", "install_path": "/home/me/Games/Heroic/Stellaris", "platform": "linux" },
+					{ "appName": "2", "install_path": "/home/me/Games/Heroic/Other Game", "platform": "linux" }
+				]
+			}"#,
+		);
+		assert_eq!(
+			paths,
+			vec![PathBuf::from("/home/me/Games/Heroic/Stellaris")]
+		);
+		assert!(parse_heroic_installed("not json").is_empty());
+	}
+
+	#[test]
+	fn test_parse_lutris_game() {
+		let path = parse_lutris_game(
+			"game:\n  exe: /home/me/Games/stellaris/stellaris\n  prefix: /home/me/Games/stellaris\nname: Stellaris\n",
+		);
+		assert_eq!(path, Some(PathBuf::from("/home/me/Games/stellaris")));
+		assert_eq!(parse_lutris_game("name: Stellaris\n"), None);
+	}
+}
@@ -1,12 +1,9 @@
 // Prevents additional console window on Windows in release, DO NOT REMOVE!!
 #![cfg_attr(not(debug_assertions), windows_subsystem = "windows")]
+// explicit returns are the style throughout this codebase
+#![allow(clippy::needless_return)]
 
-use anyhow;
-use base64::prelude::*;
-use ddsfile::Dds;
-use dirs;
 use font_kit::source::SystemSource;
-use image_dds;
 use rayon::prelude::*;
 use regex::Regex;
 use serde_json::Value;
@@ -18,16 +15,43 @@ use std::io::Read;
 use std::io::{self, BufRead, Cursor};
 use std::path::Path;
 use std::path::PathBuf;
-use std::time::{Instant, UNIX_EPOCH};
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
 use steamlocate::SteamDir;
 #[allow(unused)]
 use tauri::{Manager, path::BaseDirectory};
-use zip;
 
+use vfs::{get_files_matching_filter, DataFile, DataFs, DataLayer, FileFilter};
+
+mod borders;
+mod campaign;
+mod cli;
+mod community;
+mod config;
+mod demographics;
+mod diplomacy;
+mod economy;
+mod emblems;
+mod flags;
+mod fleets;
+mod fonts;
+mod gamestate;
+mod graph;
+mod history;
+mod install;
 mod lexer;
+mod mods;
 mod parser;
+mod sqlite;
+mod stats;
+mod textures;
+mod user_data;
+mod vfs;
 
 fn main() {
+	if let Some(exit_code) = cli::run(&env::args().collect::<Vec<String>>()) {
+		std::process::exit(exit_code);
+	}
 	tauri::Builder::default()
 		.setup(move |#[allow(unused)] app| {
 			#[cfg(feature = "electron")]
@@ -83,11 +107,40 @@ fn main() {
 			electron_closed,
 			get_stellaris_colors_cmd,
 			get_stellaris_loc_cmd,
+			get_stellaris_loc_with_fallbacks_cmd,
 			get_stellaris_install_dir_cmd,
+			get_stellaris_install_dir_candidates_cmd,
+			get_stellaris_data_roots_cmd,
 			get_stellaris_save_metadata_cmd,
 			get_stellaris_save_cmd,
+			export_sqlite_cmd,
+			export_country_stats_cmd,
+			export_campaign_stats_cmd,
+			get_campaign_history_cmd,
+			get_shortest_path_cmd,
+			get_system_distances_cmd,
+			get_capital_distances_cmd,
+			get_chokepoints_cmd,
+			get_components_cmd,
+			get_border_reports_cmd,
+			get_fleet_movements_cmd,
+			get_diplomacy_cmd,
+			get_galactic_community_cmd,
+			get_demographics_cmd,
+			get_economy_cmd,
+			export_economy_cmd,
 			get_emblem_cmd,
+			get_emblem_atlas_cmd,
+			get_flag_cmd,
+			get_emblems_cmd,
+			get_texture_cmd,
+			get_enabled_mods_cmd,
+			get_stellaris_save_mods_cmd,
 			get_fonts_cmd,
+			get_font_cmd,
+			get_glyph_coverage_cmd,
+			get_config_cmd,
+			set_config_cmd,
 			reveal_file_cmd
 		])
 		.run(tauri::generate_context!())
@@ -95,18 +148,18 @@ fn main() {
 }
 
 #[tauri::command]
-async fn get_stellaris_colors_cmd(path: String) -> Result<Vec<String>, String> {
-	let paths: Vec<PathBuf> = get_stellaris_data_dirs(Path::new(&path).to_path_buf())
-		.into_iter()
-		.map(|p| p.join("flags").join("colors.txt"))
-		.filter(|p| p.exists())
-		.collect();
-	if paths.is_empty() {
+async fn get_stellaris_colors_cmd(
+	path: String,
+	save_path: Option<String>,
+) -> Result<Vec<String>, String> {
+	let files = get_stellaris_data_fs(Path::new(&path).to_path_buf(), save_path)
+		.get_layered_files(&Path::new("flags").join("colors.txt"));
+	if files.is_empty() {
 		return Err(String::from("No color files found"));
 	}
-	let data = paths
+	let data = files
 		.into_iter()
-		.map(|p| fs::read_to_string(p).map_err(|err| err.to_string()))
+		.map(|f| f.read_to_string().map_err(|err| err.to_string()))
 		.collect();
 	return data;
 }
@@ -114,9 +167,31 @@ async fn get_stellaris_colors_cmd(path: String) -> Result<Vec<String>, String> {
 #[tauri::command]
 async fn get_stellaris_loc_cmd(
 	path: String,
-	language: String,
+	language: Option<String>,
+	fallback_languages: Option<Vec<String>>,
+	save_path: Option<String>,
 ) -> Result<HashMap<String, String>, String> {
-	return get_stellaris_loc(path, language).map_err(|err| err.to_string());
+	let config = config::load_config();
+	return get_stellaris_loc(
+		path,
+		language.unwrap_or_else(|| config.get_language()),
+		fallback_languages.unwrap_or(config.fallback_languages),
+		save_path,
+	)
+	.map(|loc| loc.locs)
+	.map_err(|err| err.to_string());
+}
+
+#[tauri::command]
+async fn get_stellaris_loc_with_fallbacks_cmd(
+	path: String,
+	language: Option<String>,
+	fallback_languages: Vec<String>,
+	save_path: Option<String>,
+) -> Result<StellarisLoc, String> {
+	let language = language.unwrap_or_else(|| config::load_config().get_language());
+	return get_stellaris_loc(path, language, fallback_languages, save_path)
+		.map_err(|err| err.to_string());
 }
 
 #[tauri::command]
@@ -130,8 +205,243 @@ async fn get_stellaris_save_cmd(path: String, filter: Value) -> Result<Value, St
 }
 
 #[tauri::command]
-async fn get_emblem_cmd(path: String, category: String, file: String) -> Result<String, String> {
-	return get_emblem(Path::new(&path).to_path_buf(), category, file).map_err(|err| err.to_string());
+async fn export_sqlite_cmd(path: String, output_path: String) -> Result<(), String> {
+	return get_stellaris_save(path, sqlite::get_filter())
+		.and_then(|state| sqlite::export_to_file(&state, Path::new(&output_path)))
+		.map_err(|err| err.to_string());
+}
+
+#[tauri::command]
+async fn export_country_stats_cmd(path: String, output_path: String) -> Result<(), String> {
+	return get_stellaris_save(path, stats::get_filter())
+		.and_then(|state| {
+			stats::export_to_file(&stats::get_country_stats(&state), Path::new(&output_path))
+		})
+		.map_err(|err| err.to_string());
+}
+
+#[tauri::command]
+async fn export_campaign_stats_cmd(dir: String, output_path: String) -> Result<(), String> {
+	return stats::get_campaign_stats(&PathBuf::from(dir))
+		.and_then(|rows| stats::export_to_file(&rows, Path::new(&output_path)))
+		.map_err(|err| err.to_string());
+}
+
+#[tauri::command]
+async fn get_campaign_history_cmd(dir: String) -> Result<history::CampaignHistory, String> {
+	return history::get_campaign_history(&PathBuf::from(dir)).map_err(|err| err.to_string());
+}
+
+#[tauri::command]
+async fn get_shortest_path_cmd(
+	path: String,
+	from: i64,
+	to: i64,
+	metric: graph::Metric,
+	include_bypasses: bool,
+) -> Result<Option<graph::GraphPath>, String> {
+	return get_graph_state(path)
+		.map(|state| {
+			graph::Graph::from_state(&state, include_bypasses).get_shortest_path(from, to, metric)
+		})
+		.map_err(|err| err.to_string());
+}
+
+#[tauri::command]
+async fn get_system_distances_cmd(
+	path: String,
+	from: Vec<i64>,
+	metric: graph::Metric,
+	include_bypasses: bool,
+) -> Result<HashMap<i64, f64>, String> {
+	return get_graph_state(path)
+		.map(|state| graph::Graph::from_state(&state, include_bypasses).get_distances(&from, metric))
+		.map_err(|err| err.to_string());
+}
+
+#[tauri::command]
+async fn get_capital_distances_cmd(
+	path: String,
+	country_id: i64,
+	metric: graph::Metric,
+	include_bypasses: bool,
+) -> Result<HashMap<i64, f64>, String> {
+	return get_graph_state(path)
+		.and_then(|state| {
+			let capital = gamestate::get_capital_system(&state, country_id)
+				.ok_or(anyhow::anyhow!("Country {} has no capital", country_id))?;
+			let graph = graph::Graph::from_state(&state, include_bypasses);
+			return Ok(graph.get_distances(&[capital], metric));
+		})
+		.map_err(|err| err.to_string());
+}
+
+#[tauri::command]
+async fn get_chokepoints_cmd(
+	path: String,
+	include_bypasses: bool,
+) -> Result<graph::Chokepoints, String> {
+	return get_graph_state(path)
+		.map(|state| graph::Graph::from_state(&state, include_bypasses).get_chokepoints())
+		.map_err(|err| err.to_string());
+}
+
+#[tauri::command]
+async fn get_components_cmd(path: String, include_bypasses: bool) -> Result<Vec<Vec<i64>>, String> {
+	return get_graph_state(path)
+		.map(|state| graph::Graph::from_state(&state, include_bypasses).get_components())
+		.map_err(|err| err.to_string());
+}
+
+#[tauri::command]
+async fn get_border_reports_cmd(
+	path: String,
+	include_bypasses: bool,
+) -> Result<Vec<borders::BorderReport>, String> {
+	return get_graph_state(path)
+		.map(|state| borders::get_border_reports(&state, include_bypasses))
+		.map_err(|err| err.to_string());
+}
+
+#[tauri::command]
+async fn get_fleet_movements_cmd(path: String) -> Result<Vec<fleets::FleetMovement>, String> {
+	return get_stellaris_save(path, fleets::get_filter())
+		.map(|state| fleets::get_fleet_movements(&state))
+		.map_err(|err| err.to_string());
+}
+
+#[tauri::command]
+async fn get_diplomacy_cmd(path: String) -> Result<diplomacy::Diplomacy, String> {
+	return get_stellaris_save(path, diplomacy::get_filter())
+		.map(|state| diplomacy::get_diplomacy(&state))
+		.map_err(|err| err.to_string());
+}
+
+#[tauri::command]
+async fn get_galactic_community_cmd(
+	path: String,
+) -> Result<Option<community::GalacticCommunity>, String> {
+	return get_stellaris_save(path, community::get_filter())
+		.map(|state| community::get_galactic_community(&state))
+		.map_err(|err| err.to_string());
+}
+
+#[tauri::command]
+async fn get_demographics_cmd(
+	path: String,
+	save_path: String,
+) -> Result<demographics::DemographicsReport, String> {
+	// jobs are defined by the game and the save's mods
+	let data_fs = get_stellaris_data_fs(PathBuf::from(path), Some(save_path.clone()));
+	let job_categories = demographics::get_job_categories(&data_fs);
+	return get_stellaris_save(save_path, demographics::get_filter())
+		.map(|state| demographics::get_demographics(&state, &job_categories))
+		.map_err(|err| err.to_string());
+}
+
+#[tauri::command]
+async fn get_economy_cmd(path: String) -> Result<Vec<economy::EconomySnapshot>, String> {
+	return get_stellaris_save(path, economy::get_filter())
+		.map(|state| economy::get_economy(&state))
+		.map_err(|err| err.to_string());
+}
+
+#[tauri::command]
+async fn export_economy_cmd(path: String, output_path: String) -> Result<(), String> {
+	return get_stellaris_save(path, economy::get_filter())
+		.and_then(|state| {
+			let rows = economy::get_economy_rows(&state, &economy::get_economy(&state));
+			return stats::export_to_file(&rows, Path::new(&output_path));
+		})
+		.map_err(|err| err.to_string());
+}
+
+#[tauri::command]
+async fn get_emblem_cmd(
+	path: String,
+	category: String,
+	file: String,
+	save_path: Option<String>,
+	size: Option<u32>,
+	variant: Option<emblems::EmblemVariant>,
+) -> Result<String, String> {
+	return get_emblem(
+		Path::new(&path).to_path_buf(),
+		category,
+		file,
+		save_path,
+		size,
+		variant,
+	)
+	.map_err(|err| err.to_string());
+}
+
+#[tauri::command]
+async fn get_emblem_atlas_cmd(
+	path: String,
+	emblems: Vec<(String, String)>,
+	save_path: Option<String>,
+) -> Result<emblems::EmblemAtlas, String> {
+	let data_fs = get_stellaris_data_fs(Path::new(&path).to_path_buf(), save_path);
+	let cache_dir = config::load_config().cache.get_dir();
+	return emblems::get_emblem_atlas(&data_fs, &emblems, cache_dir.as_deref())
+		.map_err(|err| err.to_string());
+}
+
+#[tauri::command]
+async fn get_flag_cmd(
+	path: String,
+	flag: flags::Flag,
+	size: Option<u32>,
+	save_path: Option<String>,
+) -> Result<String, String> {
+	let data_fs = get_stellaris_data_fs(Path::new(&path).to_path_buf(), save_path);
+	let colors = flags::get_flag_colors(&data_fs);
+	return flags::render_flag(&data_fs, &flag, &colors, size)
+		.and_then(|img| emblems::to_png_data_url(&img))
+		.map_err(|err| err.to_string());
+}
+
+#[tauri::command]
+async fn get_emblems_cmd(
+	path: String,
+	save_path: Option<String>,
+) -> Result<Vec<emblems::EmblemCategory>, String> {
+	let files = get_stellaris_data_paths(
+		Path::new(&path).to_path_buf(),
+		save_path,
+		Path::new("flags").to_path_buf(),
+		FileFilter::Extension(OsString::from("dds")),
+		3,
+	);
+	return Ok(emblems::list_emblems(files));
+}
+
+#[tauri::command]
+async fn get_texture_cmd(
+	path: String,
+	kind: textures::TextureKind,
+	key: String,
+	size: Option<u32>,
+	save_path: Option<String>,
+) -> Result<String, String> {
+	let data_fs = get_stellaris_data_fs(Path::new(&path).to_path_buf(), save_path);
+	return textures::resolve_texture(&data_fs, kind, &key)
+		.and_then(|file| textures::decode_texture(&file, size))
+		.and_then(|img| emblems::to_png_data_url(&img))
+		.map_err(|err| err.to_string());
+}
+
+#[tauri::command]
+async fn get_enabled_mods_cmd() -> Result<Vec<mods::ModDescriptor>, String> {
+	return user_data::get_user_data_dir()
+		.and_then(|user_data_dir| mods::get_enabled_mods(&user_data_dir))
+		.map_err(|err| err.to_string());
+}
+
+#[tauri::command]
+async fn get_stellaris_save_mods_cmd(path: String) -> Result<mods::SaveMods, String> {
+	return get_stellaris_save_mods(Path::new(&path)).map_err(|err| err.to_string());
 }
 
 #[tauri::command]
@@ -139,13 +449,52 @@ async fn get_fonts_cmd() -> Result<Vec<String>, String> {
 	return get_fonts().map_err(|err| err.to_string());
 }
 
+#[tauri::command]
+async fn get_font_cmd(
+	family: String,
+	weight: Option<f32>,
+	style: Option<fonts::FontStyle>,
+	text: Option<String>,
+) -> Result<fonts::FontData, String> {
+	return fonts::get_font_data(&family, weight, style, text.as_deref())
+		.map_err(|err| err.to_string());
+}
+
+#[tauri::command]
+async fn get_glyph_coverage_cmd(
+	family: String,
+	strings: Vec<String>,
+) -> Result<fonts::GlyphCoverage, String> {
+	return fonts::get_glyph_coverage(&family, &strings).map_err(|err| err.to_string());
+}
+
 #[tauri::command]
 async fn get_stellaris_install_dir_cmd() -> Result<String, String> {
 	return get_stellaris_install_dir()
-		.and_then(|path| Ok(path.to_string_lossy().into_owned()))
+		.map(|path| path.to_string_lossy().into_owned())
 		.map_err(|err| err.to_string());
 }
 
+#[tauri::command]
+async fn get_stellaris_install_dir_candidates_cmd() -> Vec<install::InstallDirCandidate> {
+	return install::get_install_dir_candidates();
+}
+
+#[tauri::command]
+async fn get_stellaris_data_roots_cmd() -> Vec<user_data::SearchedRoot> {
+	return user_data::search_roots();
+}
+
+#[tauri::command]
+async fn get_config_cmd() -> config::Config {
+	return config::load_config();
+}
+
+#[tauri::command]
+async fn set_config_cmd(config: config::Config) -> Result<(), String> {
+	return config::save_config(&config).map_err(|err| err.to_string());
+}
+
 #[tauri::command]
 async fn reveal_file_cmd(path: String) {
 	let _ = opener::reveal(path);
@@ -159,144 +508,145 @@ fn electron_closed(
 	Ok(())
 }
 
-fn get_steam_dir() -> anyhow::Result<PathBuf> {
-	return Ok(SteamDir::locate()?.path().to_path_buf());
-}
-
 fn get_stellaris_install_dir() -> anyhow::Result<PathBuf> {
-	let steamdir = SteamDir::locate()?;
-	let (app, lib) = steamdir
-		.find_app(281990)?
-		.ok_or(anyhow::anyhow!("Stellaris Steam install not found"))?;
-	let path = lib.resolve_app_dir(&app);
-	if path.exists() {
-		return Ok(path);
+	let candidates = install::get_install_dir_candidates();
+	if let Some(candidate) = candidates.iter().find(|c| c.valid) {
+		println!(
+			"found Stellaris install ({}): {}",
+			candidate.reason,
+			candidate.path.display()
+		);
+		return Ok(candidate.path.clone());
+	} else if candidates.is_empty() {
+		anyhow::bail!("Stellaris install not found");
 	} else {
-		anyhow::bail!("Stellaris path does not exist: {}", path.display());
+		anyhow::bail!(
+			"No valid Stellaris install found, checked: {}",
+			candidates
+				.iter()
+				.map(|c| c.path.display().to_string())
+				.collect::<Vec<String>>()
+				.join(", ")
+		);
 	}
 }
 
-fn get_stellaris_user_data_dir() -> PathBuf {
-	match env::consts::OS {
-		"linux" => {
-			return dirs::home_dir()
-				.unwrap()
-				.join(".local/share/Paradox Interactive/Stellaris");
-		}
-		"macos" => {
-			return dirs::document_dir()
-				.unwrap()
-				.join("Paradox Interactive/Stellaris");
-		}
-		"windows" => {
-			return dirs::document_dir()
-				.unwrap()
-				.join("Paradox Interactive\\Stellaris");
-		}
-		_ => panic!("unsupported OS"),
-	}
-}
-
-fn get_steam_user_data_dirs() -> anyhow::Result<Vec<PathBuf>> {
-	let steam_user_data_dir = get_steam_dir()?.join("userdata");
-	return get_sub_dirs(&steam_user_data_dir);
+fn get_steam_workshop_dirs() -> anyhow::Result<Vec<PathBuf>> {
+	return Ok(
+		SteamDir::locate()?
+			.library_paths()?
+			.into_iter()
+			.map(|path| path.join("steamapps/workshop/content/281990"))
+			.filter(|path| path.is_dir())
+			.collect(),
+	);
 }
 
-fn get_mod_path(enabled_mod: &serde_json::Value) -> anyhow::Result<PathBuf> {
-	let user_data_dir = get_stellaris_user_data_dir();
-	let enabled_mod_descriptor = user_data_dir.join(
-		enabled_mod
-			.as_str()
-			.ok_or(anyhow::anyhow!("Expected enabled_mods to be string array"))?,
+/// Matches the mods listed in the save's meta to installed mods
+fn get_stellaris_save_mods(save_path: &Path) -> anyhow::Result<mods::SaveMods> {
+	let file = fs::File::open(save_path)?;
+	let mut archive = zip::ZipArchive::new(io::BufReader::new(file))?;
+	let mut meta = String::new();
+	archive.by_name("meta")?.read_to_string(&mut meta)?;
+	let save_mod_names = mods::get_save_meta_mod_names(&meta)?;
+	let installed_mods = mods::get_installed_mods(
+		&user_data::get_user_data_dir()?,
+		&get_steam_workshop_dirs().unwrap_or_default(),
 	);
-	let enabled_mod_descriptor = fs::File::open(enabled_mod_descriptor)?;
-	let enabled_mod_descriptor = io::read_to_string(enabled_mod_descriptor)?;
-	let path_re = Regex::new(r#"(?m)^path="(.*)"$"#).unwrap();
-	let mod_path = path_re
-		.captures(&enabled_mod_descriptor)
-		.map(|c| c.extract::<1>().1[0].to_string())
-		.unwrap_or_default();
-	return Ok(Path::new(&mod_path).to_path_buf());
-}
-
-fn get_enabled_mod_dirs() -> anyhow::Result<Vec<PathBuf>> {
-	let user_data_dir = get_stellaris_user_data_dir();
-	let dlc_load = user_data_dir.join("dlc_load.json");
-	let dlc_load = fs::File::open(dlc_load)?;
-	let dlc_load: serde_json::Value = serde_json::from_reader(dlc_load)?;
-	let enabled_mods = dlc_load
-		.get("enabled_mods")
-		.ok_or(anyhow::anyhow!("Expected dlc_load to contain enabled_mods"))?;
-	let mut mod_dirs = vec![];
-	for enabled_mod in enabled_mods
-		.as_array()
-		.ok_or(anyhow::anyhow!("Expected enabled_mods to be string array"))?
-	{
-		match get_mod_path(enabled_mod) {
-			Ok(mod_path) => mod_dirs.push(mod_path),
-			_ => (),
-		}
+	let save_mods = mods::resolve_save_mods(&save_mod_names, &installed_mods);
+	if !save_mods.missing.is_empty() {
+		println!("mods used by save not installed: {:?}", save_mods.missing);
 	}
-	return Ok(mod_dirs);
+	return Ok(save_mods);
 }
 
-fn get_stellaris_data_dirs(install_path: PathBuf) -> Vec<PathBuf> {
-	let mut dirs = vec![install_path];
-	match get_enabled_mod_dirs() {
-		Ok(mod_dirs) => {
-			for dir in mod_dirs {
-				dirs.push(dir);
-			}
-		}
-		_ => (),
+/// The mod layers of each save viewed, with the save's modified time they were resolved for
+///
+/// Resolving reads the save and scans every installed mod, and the frontend calls commands using
+/// the data fs (eg get_emblem_cmd) once per emblem, so this would otherwise happen hundreds of times per map
+static SAVE_MOD_LAYERS: OnceLock<Mutex<HashMap<PathBuf, SaveModLayers>>> = OnceLock::new();
+
+type SaveModLayers = (SystemTime, Vec<Arc<DataLayer>>);
+
+/// If save_path is provided, the mods listed in that save are used instead of the currently enabled mods
+fn get_stellaris_data_fs(install_path: PathBuf, save_path: Option<String>) -> DataFs {
+	let mut layers = vec![Arc::new(DataLayer::new(install_path, vec![]))];
+	match save_path {
+		Some(save_path) => layers.extend(get_save_mod_layers(Path::new(&save_path))),
+		None => match user_data::get_user_data_dir()
+			.and_then(|user_data_dir| mods::get_enabled_mods(&user_data_dir))
+		{
+			Ok(mods) => layers.extend(get_mod_layers(mods).into_iter().map(Arc::new)),
+			Err(err) => println!(
+				"failed to load enabled mods, using only vanilla data: {}",
+				err
+			),
+		},
+	}
+	for extra_mod_dir in config::load_config().extra_mod_dirs {
+		layers.push(Arc::new(DataLayer::new(extra_mod_dir, vec![])));
 	}
-	return dirs;
+	return DataFs::new(layers);
 }
 
-fn get_sub_dirs(path: &PathBuf) -> anyhow::Result<Vec<PathBuf>> {
-	let mut sub_dirs: Vec<PathBuf> = Vec::new();
-	if path.is_dir() {
-		for entry in fs::read_dir(path)? {
-			let entry = entry?;
-			let path = entry.path();
-			if path.is_dir() {
-				sub_dirs.push(path.to_path_buf());
-			}
+/// Failures are cached too, so they're only logged once per save;
+/// the frontend gets the error itself from get_stellaris_save_mods_cmd
+fn get_save_mod_layers(save_path: &Path) -> Vec<Arc<DataLayer>> {
+	let modified = fs::metadata(save_path)
+		.and_then(|metadata| metadata.modified())
+		.ok();
+	let cache = SAVE_MOD_LAYERS.get_or_init(Default::default);
+	if let (Some(modified), Some((cached_modified, layers))) =
+		(modified, cache.lock().unwrap().get(save_path))
+	{
+		if modified == *cached_modified {
+			return layers.clone();
 		}
 	}
-	Ok(sub_dirs)
-}
-
-enum FileFilter {
-	Extension(OsString),
+	// resolved without the lock, so a slow launcher db or mod archive doesn't hold up other saves
+	let layers: Vec<Arc<DataLayer>> = match get_stellaris_save_mods(save_path) {
+		Ok(save_mods) => get_mod_layers(save_mods.resolved)
+			.into_iter()
+			.map(Arc::new)
+			.collect(),
+		Err(err) => {
+			println!(
+				"failed to resolve mods of {}, using only vanilla data: {}",
+				save_path.display(),
+				err
+			);
+			vec![]
+		}
+	};
+	if let Some(modified) = modified {
+		cache
+			.lock()
+			.unwrap()
+			.insert(save_path.to_path_buf(), (modified, layers.clone()));
+	}
+	return layers;
 }
 
-fn get_files_matching_filter(
-	path: &PathBuf,
-	filter: &FileFilter,
-	depth: u8,
-) -> anyhow::Result<Vec<PathBuf>> {
-	let mut files: Vec<PathBuf> = Vec::new();
-	if path.is_dir() {
-		for entry in fs::read_dir(path)? {
-			let entry = entry?;
-			let path = entry.path();
-			if path.is_dir() && (depth > 1 || depth == 0) {
-				let mut sub_dir_files = get_files_matching_filter(&path, filter, depth - 1)?;
-				files.append(&mut sub_dir_files);
-			} else {
-				match filter {
-					FileFilter::Extension(filter_ext) => match path.extension() {
-						Some(ext) if ext == filter_ext => files.push(path),
-						_ => (),
-					},
-				}
+fn get_mod_layers(mods: Vec<mods::ModDescriptor>) -> Vec<DataLayer> {
+	let mut layers = vec![];
+	for descriptor in mods {
+		if let Some(path) = descriptor.path {
+			layers.push(DataLayer::new(path, descriptor.replace_paths));
+		} else if let Some(archive) = descriptor.archive {
+			match DataLayer::from_archive(archive, descriptor.replace_paths) {
+				Ok(layer) => layers.push(layer),
+				Err(err) => println!(
+					"failed to open mod archive for {}: {}",
+					descriptor.name, err
+				),
 			}
 		}
 	}
-	Ok(files)
+	return layers;
 }
 
+/// The filter lists the sections to keep, and the parser skips everything else in the gamestate,
+/// so each module that reads saves has a get_filter with just the sections it uses
 fn get_stellaris_save(path: String, filter: Value) -> anyhow::Result<Value> {
 	let now = Instant::now();
 	let file = fs::File::open(path)?;
@@ -314,6 +664,27 @@ fn get_stellaris_save(path: String, filter: Value) -> anyhow::Result<Value> {
 	return Ok(parsed);
 }
 
+/// The last save parsed for the graph and border commands, since the frontend calls several of them for one save
+static GRAPH_STATE: OnceLock<Mutex<Option<CachedState>>> = OnceLock::new();
+
+type CachedState = (String, SystemTime, Arc<Value>);
+
+/// Parsed with the borders filter, which includes everything the graph reads, so they share one parse
+///
+/// Only the last save is kept, so switching saves doesn't hold on to every parsed gamestate
+fn get_graph_state(path: String) -> anyhow::Result<Arc<Value>> {
+	let modified = fs::metadata(&path)?.modified()?;
+	let cache = GRAPH_STATE.get_or_init(Default::default);
+	if let Some((cached_path, cached_modified, state)) = cache.lock().unwrap().as_ref() {
+		if *cached_path == path && *cached_modified == modified {
+			return Ok(state.clone());
+		}
+	}
+	let state = Arc::new(get_stellaris_save(path.clone(), borders::get_filter())?);
+	*cache.lock().unwrap() = Some((path, modified, state.clone()));
+	return Ok(state);
+}
+
 #[derive(serde::Serialize)]
 struct StellarisSave {
 	path: String,
@@ -363,20 +734,16 @@ impl StellarisSave {
 }
 
 fn get_stellaris_save_metadata() -> anyhow::Result<Vec<Vec<StellarisSave>>> {
-	let saves: Vec<Vec<StellarisSave>> = get_steam_user_data_dirs()
-		.unwrap_or(Vec::new())
-		.iter()
-		.map(|path| path.join("281990").join("remote").join("save games"))
-		.chain(std::iter::once(
-			get_stellaris_user_data_dir().join("save games"),
-		))
-		.flat_map(|path| get_sub_dirs(&path).unwrap_or_default())
+	let saves: Vec<Vec<StellarisSave>> = user_data::get_save_dir_candidates()
+		.into_iter()
+		.map(|(path, _)| path)
+		.flat_map(|path| user_data::get_sub_dirs(&path))
 		.map(|path| {
 			let files = Vec::from_iter(
 				get_files_matching_filter(&path, &FileFilter::Extension(OsString::from("sav")), 1)
-					.unwrap_or(Vec::new())
+					.unwrap_or_default()
 					.iter()
-					.map(|path| StellarisSave::from_path_or_default(path)),
+					.map(StellarisSave::from_path_or_default),
 			);
 			return files;
 		})
@@ -386,55 +753,64 @@ fn get_stellaris_save_metadata() -> anyhow::Result<Vec<Vec<StellarisSave>>> {
 
 fn get_stellaris_data_paths(
 	install_path: PathBuf,
+	save_path: Option<String>,
 	data_relative_dir: PathBuf,
 	filter: FileFilter,
 	depth: u8,
-) -> Vec<PathBuf> {
-	let data_root_dirs = get_stellaris_data_dirs(install_path);
-	let mut file_path_to_root_dir: HashMap<PathBuf, PathBuf> = HashMap::new();
-	for data_root_dir in data_root_dirs {
-		let dir = data_root_dir.join(&data_relative_dir);
-		match get_files_matching_filter(&dir, &filter, depth) {
-			Ok(files) => {
-				for file in files {
-					file_path_to_root_dir.insert(
-						file
-							.strip_prefix(&data_root_dir)
-							.expect("data file is not descendant of data root dir")
-							.to_path_buf(),
-						data_root_dir.clone(),
-					);
-				}
-			}
-			_ => (),
-		}
-	}
-	let mut entries: Vec<(PathBuf, PathBuf)> = file_path_to_root_dir.into_iter().collect();
-	entries.sort_by(|a, b| a.0.cmp(&b.0));
-	return entries
-		.into_iter()
-		.map(|(child, base)| base.join(child))
-		.collect();
+) -> Vec<DataFile> {
+	return get_stellaris_data_fs(install_path, save_path).get_files(
+		&data_relative_dir,
+		&filter,
+		depth,
+	);
+}
+
+#[derive(serde::Serialize)]
+struct StellarisLoc {
+	locs: HashMap<String, String>,
+	fallbacks: Vec<StellarisLocFallback>,
+}
+
+#[derive(serde::Serialize)]
+struct StellarisLocFallback {
+	path: String,
+	language: String,
+	count: usize,
 }
 
-fn get_stellaris_loc(path: String, language: String) -> anyhow::Result<HashMap<String, String>> {
+/// Index into the language chain (if the file matched one) and the file's key-value pairs
+type LanguageLocs = (Option<usize>, HashMap<String, String>);
+
+fn get_stellaris_loc(
+	path: String,
+	language: String,
+	fallback_languages: Vec<String>,
+	save_path: Option<String>,
+) -> anyhow::Result<StellarisLoc> {
 	use std::time::Instant;
 	let now = Instant::now();
 
-	let loc_file_paths = get_stellaris_data_paths(
+	let mut loc_files = get_stellaris_data_paths(
 		Path::new(&path).to_path_buf(),
+		save_path,
 		Path::new("localisation").to_path_buf(),
 		FileFilter::Extension(OsString::from("yml")),
 		8,
 	);
-	if loc_file_paths.is_empty() {
+	if loc_files.is_empty() {
 		return Err(anyhow::anyhow!("No localisation files found"));
 	}
-	let mut locs: HashMap<String, String> = HashMap::new();
-	let locs_by_file: Vec<anyhow::Result<HashMap<String, String>>> = loc_file_paths
+	// keys in localisation/replace (or localisation/<language>/replace) take priority over all others,
+	// regardless of load order, so read those files last
+	loc_files.sort_by_key(|f| is_loc_replace_file(&f.relative_path));
+	// index 0 is the requested language, followed by the fallbacks in priority order
+	let languages: Vec<&String> = std::iter::once(&language)
+		.chain(fallback_languages.iter())
+		.collect();
+	let locs_by_file: Vec<anyhow::Result<LanguageLocs>> = loc_files
 		.par_iter()
-		.map(|path| {
-			let mut buf_reader = io::BufReader::new(fs::File::open(path)?);
+		.map(|file| {
+			let mut buf_reader = io::BufReader::new(Cursor::new(file.read()?));
 			let mut first_line = String::new();
 			let _ = buf_reader.read_line(&mut first_line)?;
 			// skip BOM
@@ -448,7 +824,10 @@ fn get_stellaris_loc(path: String, language: String) -> anyhow::Result<HashMap<S
 				let _ = buf_reader.read_line(&mut first_line)?;
 			}
 			let mut file_locs: HashMap<String, String> = HashMap::new();
-			if first_line.contains(language.as_str()) {
+			let file_language = languages
+				.iter()
+				.position(|language| first_line.contains(language.as_str()));
+			if file_language.is_some() {
 				let re = Regex::new(r#"(?m)^\s*([\w\.\-]+)\s*:\d*\s*"(.*)".*$"#).unwrap();
 				let mut raw_content = String::new();
 				let _ = buf_reader.read_to_string(&mut raw_content)?;
@@ -456,36 +835,82 @@ fn get_stellaris_loc(path: String, language: String) -> anyhow::Result<HashMap<S
 					file_locs.insert(key.to_string(), value.to_string());
 				}
 			}
-			return Ok(file_locs);
+			return Ok((file_language, file_locs));
 		})
 		.collect();
 
-	for file_locs in locs_by_file {
-		locs.extend(file_locs?.drain())
+	let mut locs_by_language: Vec<HashMap<String, (String, usize)>> =
+		languages.iter().map(|_| HashMap::new()).collect();
+	for (file_index, file_locs) in locs_by_file.into_iter().enumerate() {
+		let (file_language, mut file_locs) = file_locs?;
+		if let Some(file_language) = file_language {
+			locs_by_language[file_language].extend(
+				file_locs
+					.drain()
+					.map(|(key, value)| (key, (value, file_index))),
+			);
+		}
 	}
 
-	println!("read loc in: {}", now.elapsed().as_millis());
-	return Ok(locs);
-}
-
-fn get_emblem(install_path: PathBuf, category: String, file: String) -> anyhow::Result<String> {
-	let mut dirs = get_stellaris_data_dirs(install_path).to_owned();
-	dirs.reverse();
-	for dir in dirs {
-		let path = dir.join("flags").join(&category).join("map").join(&file);
-		if path.exists() {
-			let mut reader = std::fs::File::open(path)?;
-			let dds = Dds::read(&mut reader)?;
-			let img = image_dds::image_from_dds(&dds, 0)?;
-			let mut bytes: Vec<u8> = Vec::new();
-			img.write_to(&mut Cursor::new(&mut bytes), image::ImageOutputFormat::Png)?;
-			return Ok(format!(
-				"data:image/png;base64,{}",
-				BASE64_STANDARD.encode(bytes)
-			));
+	let mut locs: HashMap<String, String> = HashMap::new();
+	let mut fallbacks = vec![];
+	for (language_index, language_locs) in locs_by_language.into_iter().enumerate() {
+		let mut fallback_counts: HashMap<usize, usize> = HashMap::new();
+		for (key, (value, file_index)) in language_locs {
+			if let std::collections::hash_map::Entry::Vacant(entry) = locs.entry(key) {
+				entry.insert(value);
+				if language_index > 0 {
+					*fallback_counts.entry(file_index).or_default() += 1;
+				}
+			}
+		}
+		let mut fallback_counts: Vec<(usize, usize)> = fallback_counts.into_iter().collect();
+		fallback_counts.sort();
+		for (file_index, count) in fallback_counts {
+			fallbacks.push(StellarisLocFallback {
+				path: loc_files[file_index].path().to_string_lossy().into_owned(),
+				language: languages[language_index].clone(),
+				count,
+			});
 		}
 	}
-	return Err(anyhow::anyhow!("No data dir contained emblem"));
+
+	println!("read loc in: {}", now.elapsed().as_millis());
+	if !fallbacks.is_empty() {
+		println!(
+			"filled {} missing loc keys from fallback languages",
+			fallbacks.iter().map(|f| f.count).sum::<usize>()
+		);
+	}
+	return Ok(StellarisLoc { locs, fallbacks });
+}
+
+fn is_loc_replace_file(relative_path: &Path) -> bool {
+	return relative_path
+		.parent()
+		.map(|dir| dir.components().any(|c| c.as_os_str() == "replace"))
+		.unwrap_or(false);
+}
+
+fn get_emblem(
+	install_path: PathBuf,
+	category: String,
+	file: String,
+	save_path: Option<String>,
+	size: Option<u32>,
+	variant: Option<emblems::EmblemVariant>,
+) -> anyhow::Result<String> {
+	let emblem = get_stellaris_data_fs(install_path, save_path)
+		.resolve(&emblems::get_emblem_path(&category, &file))
+		.ok_or(anyhow::anyhow!("No data dir contained emblem"))?;
+	let mut img = match size {
+		Some(size) => emblems::decode_dds_at_size(&emblem, size)?,
+		None => emblems::decode_dds(&emblem)?,
+	};
+	if let Some(variant) = variant {
+		img = emblems::apply_variant(&img, variant);
+	}
+	return emblems::to_png_data_url(&img);
 }
 
 fn get_fonts() -> anyhow::Result<Vec<String>> {
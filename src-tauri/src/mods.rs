@@ -0,0 +1,592 @@
+use crate::lexer::Token;
+use crate::parser;
+use crate::vfs::{get_files_matching_filter, FileFilter};
+use logos::Logos;
+use rusqlite::{Connection, OpenFlags, OptionalExtension};
+use std::ffi::OsString;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// The contents of a mod's `.mod` descriptor file
+#[derive(serde::Serialize, Default, Clone)]
+pub struct ModDescriptor {
+	pub name: String,
+	/// The descriptor's path relative to the user data dir, eg "mod/ugc_123456.mod"
+	pub descriptor: Option<String>,
+	pub path: Option<PathBuf>,
+	pub archive: Option<PathBuf>,
+	pub remote_file_id: Option<String>,
+	pub replace_paths: Vec<PathBuf>,
+}
+
+impl ModDescriptor {
+	pub fn parse(content: &str) -> anyhow::Result<Self> {
+		let content = parser::normalize_text(content);
+		let values = parse_raw_values(&content)?;
+		let get_string = |key: &str| {
+			return values.iter().find_map(|(k, value)| match value {
+				RawValue::String(s) if *k == key && !s.is_empty() => Some(s.to_string()),
+				_ => None,
+			});
+		};
+		let name = get_string("name").unwrap_or_default();
+		let path = get_string("path").map(PathBuf::from);
+		let archive = get_string("archive").map(PathBuf::from);
+		let remote_file_id = get_string("remote_file_id");
+		let replace_paths = values
+			.iter()
+			.filter_map(|(key, value)| match value {
+				RawValue::String(s) if *key == "replace_path" && !s.is_empty() => Some(PathBuf::from(s)),
+				_ => None,
+			})
+			.collect();
+		return Ok(ModDescriptor {
+			name,
+			descriptor: None,
+			path,
+			archive,
+			remote_file_id,
+			replace_paths,
+		});
+	}
+
+	pub fn from_path(path: &Path) -> anyhow::Result<Self> {
+		let bytes = fs::read(path)?;
+		return Self::parse(&String::from_utf8_lossy(&bytes));
+	}
+
+	/// Loads the descriptor at user_data_dir/descriptor (as listed in dlc_load.json or the launcher db)
+	pub fn from_user_data_dir(user_data_dir: &Path, descriptor: &str) -> anyhow::Result<Self> {
+		let mut mod_descriptor = Self::from_path(&user_data_dir.join(descriptor))?;
+		mod_descriptor.descriptor = Some(descriptor.to_string());
+		mod_descriptor.resolve_relative_paths(user_data_dir);
+		return Ok(mod_descriptor);
+	}
+
+	/// Whether a mod listed in a save (by name, descriptor, or workshop id) refers to this mod
+	fn matches(&self, save_mod: &str) -> bool {
+		return self.name == save_mod
+			|| self.descriptor.as_deref() == Some(save_mod)
+			|| self.remote_file_id.as_deref() == Some(save_mod);
+	}
+
+	/// Local mods often use paths relative to the user data dir, eg path="mod/my_mod"
+	fn resolve_relative_paths(&mut self, user_data_dir: &Path) {
+		for path in [&mut self.path, &mut self.archive].into_iter().flatten() {
+			if path.is_relative() {
+				*path = user_data_dir.join(&path);
+			}
+		}
+	}
+}
+
+enum RawValue<'a> {
+	String(&'a str),
+	/// A block of plain values, eg tags={ "Graphics" "Gameplay" }
+	List(Vec<&'a str>),
+	/// Any other block, eg one with assignments inside
+	Block,
+}
+
+/// The top-level values of a descriptor or save meta, exactly as written
+///
+/// The parser turns numeric-looking values into numbers, which would change eg "1.10" to 1.1,
+/// and these files only hold strings and lists of strings, so the tokens are read directly
+fn parse_raw_values(content: &str) -> anyhow::Result<Vec<(&str, RawValue<'_>)>> {
+	let mut values = vec![];
+	let mut key = None;
+	let mut assigning = false;
+	let mut depth = 0;
+	let mut list = Some(vec![]);
+	for token in Token::lexer(content) {
+		let token = token.map_err(|_| anyhow::anyhow!("Invalid token in mod file"))?;
+		if depth > 0 {
+			match token {
+				Token::Open => {
+					depth += 1;
+					list = None;
+				}
+				Token::Close => {
+					depth -= 1;
+					if depth == 0 {
+						let value = list.take().map_or(RawValue::Block, RawValue::List);
+						values.extend(key.take().map(|key| (key, value)));
+						list = Some(vec![]);
+					}
+				}
+				Token::Equals => list = None,
+				Token::Text(text) | Token::String(text) if depth == 1 => {
+					if let Some(list) = &mut list {
+						list.push(text);
+					}
+				}
+				_ => (),
+			}
+			continue;
+		}
+		match token {
+			Token::Text(text) | Token::String(text) => {
+				if assigning {
+					values.extend(key.take().map(|key| (key, RawValue::String(text))));
+					assigning = false;
+				} else {
+					key = Some(text);
+				}
+			}
+			Token::Equals => assigning = key.is_some(),
+			Token::Open => {
+				depth = 1;
+				if !assigning {
+					key = None;
+				}
+				assigning = false;
+			}
+			Token::Close => anyhow::bail!("Unmatched closing brace in mod file"),
+			Token::Comment => (),
+		}
+	}
+	return Ok(values);
+}
+
+/// The mod names listed in a save's meta file, in load order
+pub fn get_save_meta_mod_names(meta: &str) -> anyhow::Result<Vec<String>> {
+	let meta = parser::normalize_text(meta);
+	let values = parse_raw_values(&meta)?;
+	return Ok(
+		values
+			.into_iter()
+			.find_map(|(key, value)| match value {
+				RawValue::List(names) if key == "mods" => Some(names),
+				_ => None,
+			})
+			.unwrap_or_default()
+			.into_iter()
+			.map(String::from)
+			.collect(),
+	);
+}
+
+/// Mods enabled in the launcher, in load order
+///
+/// The launcher's active playset is the source of truth; dlc_load.json is only used if the
+/// launcher database is missing or has no active playset (eg older launcher versions)
+pub fn get_enabled_mods(user_data_dir: &Path) -> anyhow::Result<Vec<ModDescriptor>> {
+	return match get_launcher_playset_mods(user_data_dir) {
+		Ok(mods) => Ok(mods),
+		Err(err) => {
+			println!("falling back to dlc_load.json: {}", err);
+			get_dlc_load_mods(user_data_dir)
+		}
+	};
+}
+
+fn get_launcher_playset_mods(user_data_dir: &Path) -> anyhow::Result<Vec<ModDescriptor>> {
+	let db_path = user_data_dir.join("launcher-v2.sqlite");
+	if !db_path.exists() {
+		anyhow::bail!("Launcher database not found: {}", db_path.display());
+	}
+	let connection = Connection::open_with_flags(db_path, OpenFlags::SQLITE_OPEN_READ_ONLY)?;
+	let playset_id: String = connection
+		.query_row("SELECT id FROM playsets WHERE isActive = 1", [], |row| {
+			row.get(0)
+		})
+		.optional()?
+		.ok_or(anyhow::anyhow!("Launcher database has no active playset"))?;
+	let mut statement = connection.prepare(
+		"SELECT mods.gameRegistryId, mods.displayName, mods.dirPath, mods.archivePath
+		FROM playsets_mods
+		JOIN mods ON mods.id = playsets_mods.modId
+		WHERE playsets_mods.playsetId = ?1 AND playsets_mods.enabled = 1
+		ORDER BY playsets_mods.position",
+	)?;
+	let rows = statement.query_map([playset_id], |row| {
+		return Ok((
+			row.get::<_, Option<String>>(0)?,
+			row.get::<_, Option<String>>(1)?,
+			row.get::<_, Option<String>>(2)?,
+			row.get::<_, Option<String>>(3)?,
+		));
+	})?;
+	let mut mods = vec![];
+	for row in rows {
+		let (game_registry_id, display_name, dir_path, archive_path) = row?;
+		// the launcher db doesn't store replace_path, so we still need the descriptor if it exists
+		let mut mod_descriptor = game_registry_id
+			.as_ref()
+			.and_then(|id| ModDescriptor::from_user_data_dir(user_data_dir, id).ok())
+			.unwrap_or_default();
+		mod_descriptor.descriptor = game_registry_id;
+		if mod_descriptor.name.is_empty() {
+			mod_descriptor.name = display_name.unwrap_or_default();
+		}
+		// the launcher always stores absolute paths, so prefer them over the descriptor's
+		if let Some(dir_path) = dir_path.filter(|p| !p.is_empty()) {
+			mod_descriptor.path = Some(PathBuf::from(dir_path));
+		}
+		if let Some(archive_path) = archive_path.filter(|p| !p.is_empty()) {
+			mod_descriptor.archive = Some(PathBuf::from(archive_path));
+		}
+		mods.push(mod_descriptor);
+	}
+	return Ok(mods);
+}
+
+fn get_dlc_load_mods(user_data_dir: &Path) -> anyhow::Result<Vec<ModDescriptor>> {
+	let dlc_load = user_data_dir.join("dlc_load.json");
+	let dlc_load = fs::File::open(dlc_load)?;
+	let dlc_load: serde_json::Value = serde_json::from_reader(dlc_load)?;
+	let enabled_mods = dlc_load
+		.get("enabled_mods")
+		.ok_or(anyhow::anyhow!("Expected dlc_load to contain enabled_mods"))?;
+	let mut mods = vec![];
+	for enabled_mod in enabled_mods
+		.as_array()
+		.ok_or(anyhow::anyhow!("Expected enabled_mods to be string array"))?
+	{
+		let descriptor = enabled_mod
+			.as_str()
+			.ok_or(anyhow::anyhow!("Expected enabled_mods to be string array"))?;
+		if let Ok(mod_descriptor) = ModDescriptor::from_user_data_dir(user_data_dir, descriptor) {
+			mods.push(mod_descriptor);
+		}
+	}
+	return Ok(mods);
+}
+
+/// Every mod with a descriptor in the user data dir's mod folder or in a workshop content dir
+///
+/// Mods in the user data dir come first, so they win when a mod is installed in both
+pub fn get_installed_mods(user_data_dir: &Path, workshop_dirs: &[PathBuf]) -> Vec<ModDescriptor> {
+	let mut mods = vec![];
+	let descriptors = get_files_matching_filter(
+		&user_data_dir.join("mod"),
+		&FileFilter::Extension(OsString::from("mod")),
+		1,
+	)
+	.unwrap_or_default();
+	for descriptor_path in descriptors {
+		if let Some(file_name) = descriptor_path.file_name() {
+			let descriptor = format!("mod/{}", file_name.to_string_lossy());
+			if let Ok(mod_descriptor) = ModDescriptor::from_user_data_dir(user_data_dir, &descriptor) {
+				mods.push(mod_descriptor);
+			}
+		}
+	}
+	for workshop_dir in workshop_dirs {
+		let item_dirs = fs::read_dir(workshop_dir)
+			.into_iter()
+			.flatten()
+			.flatten()
+			.map(|entry| entry.path())
+			.filter(|path| path.is_dir());
+		for item_dir in item_dirs {
+			// workshop descriptors usually omit path, since the content dir is the mod
+			if let Ok(mut mod_descriptor) = ModDescriptor::from_path(&item_dir.join("descriptor.mod")) {
+				match &mod_descriptor.archive {
+					Some(archive) if archive.is_relative() => {
+						mod_descriptor.archive = Some(item_dir.join(archive));
+					}
+					Some(_) => (),
+					None => mod_descriptor.path = Some(item_dir.clone()),
+				}
+				if mod_descriptor.remote_file_id.is_none() {
+					mod_descriptor.remote_file_id = item_dir
+						.file_name()
+						.map(|id| id.to_string_lossy().into_owned());
+				}
+				mods.push(mod_descriptor);
+			}
+		}
+	}
+	return mods;
+}
+
+#[derive(serde::Serialize)]
+pub struct SaveMods {
+	/// Installed mods matching the save's mod list, in the save's load order
+	pub resolved: Vec<ModDescriptor>,
+	/// Entries from the save's mod list that aren't installed
+	pub missing: Vec<String>,
+}
+
+pub fn resolve_save_mods(save_mods: &[String], installed_mods: &[ModDescriptor]) -> SaveMods {
+	let mut resolved = vec![];
+	let mut missing = vec![];
+	for save_mod in save_mods {
+		match installed_mods.iter().find(|m| m.matches(save_mod)) {
+			Some(installed_mod) => resolved.push(installed_mod.clone()),
+			None => missing.push(save_mod.clone()),
+		}
+	}
+	return SaveMods { resolved, missing };
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use tempfile::TempDir;
+
+	/// A minimal copy of the launcher-v2.sqlite schema, with only the columns we read
+	fn create_launcher_db(user_data_dir: &Path) -> Connection {
+		let connection = Connection::open(user_data_dir.join("launcher-v2.sqlite")).unwrap();
+		connection
+			.execute_batch(
+				"CREATE TABLE playsets (id TEXT PRIMARY KEY, name TEXT, isActive BOOLEAN);
+				CREATE TABLE mods (
+					id TEXT PRIMARY KEY,
+					gameRegistryId TEXT,
+					displayName TEXT,
+					dirPath TEXT,
+					archivePath TEXT
+				);
+				CREATE TABLE playsets_mods (
+					playsetId TEXT,
+					modId TEXT,
+					enabled BOOLEAN,
+					position INTEGER
+				);",
+			)
+			.unwrap();
+		return connection;
+	}
+
+	fn write_descriptor(user_data_dir: &Path, file: &str, content: &str) {
+		let path = user_data_dir.join(file);
+		fs::create_dir_all(path.parent().unwrap()).unwrap();
+		fs::write(path, content).unwrap();
+	}
+
+	fn names(mods: &[ModDescriptor]) -> Vec<&str> {
+		return mods.iter().map(|m| m.name.as_str()).collect();
+	}
+
+	#[test]
+	fn test_parse_descriptor() {
+		let descriptor = ModDescriptor::parse(
+			r#"
+				version="1.0"
+				tags={
+					"Graphics"
+				}
+				name="Better Flags"
+				replace_path="flags/backgrounds"
+				replace_path="common/country_types"
+				supported_version="3.12.*"
+				path="C:/Users/Me/Documents/Paradox Interactive/Stellaris/mod/better_flags"
+				remote_file_id="123456"
+			"#,
+		)
+		.unwrap();
+		assert_eq!(descriptor.name, "Better Flags");
+		assert_eq!(
+			descriptor.path,
+			Some(PathBuf::from(
+				"C:/Users/Me/Documents/Paradox Interactive/Stellaris/mod/better_flags"
+			))
+		);
+		assert_eq!(
+			descriptor.replace_paths,
+			vec![
+				PathBuf::from("flags/backgrounds"),
+				PathBuf::from("common/country_types")
+			]
+		);
+	}
+
+	#[test]
+	fn test_parse_crlf_descriptor() {
+		let descriptor = ModDescriptor::parse(
+			"\u{feff}version=\"1.0\"\r\ntags={\r\n\t\"Graphics\"\r\n}\r\nname=\"Better Flags\"\r\nreplace_path=\"flags/backgrounds\"\r\nremote_file_id=\"123456\"\r\n",
+		)
+		.unwrap();
+		assert_eq!(descriptor.name, "Better Flags");
+		assert_eq!(
+			descriptor.replace_paths,
+			vec![PathBuf::from("flags/backgrounds")]
+		);
+		assert_eq!(descriptor.remote_file_id.as_deref(), Some("123456"));
+	}
+
+	#[test]
+	fn test_parse_descriptor_without_path() {
+		let descriptor = ModDescriptor::parse(r#"name="1984""#).unwrap();
+		assert_eq!(descriptor.name, "1984");
+		assert_eq!(descriptor.path, None);
+		assert!(descriptor.replace_paths.is_empty());
+	}
+
+	#[test]
+	fn test_parse_descriptor_keeps_values_as_written() {
+		let descriptor = ModDescriptor::parse(
+			r#"
+				name="2.10"
+				version="3.0"
+				supported_version="1.10"
+				remote_file_id="0123"
+			"#,
+		)
+		.unwrap();
+		assert_eq!(descriptor.name, "2.10");
+		assert_eq!(descriptor.remote_file_id.as_deref(), Some("0123"));
+
+		let values = parse_raw_values(r#"version="3.0" supported_version=1.10"#).unwrap();
+		assert!(matches!(values[0], ("version", RawValue::String("3.0"))));
+		assert!(matches!(
+			values[1],
+			("supported_version", RawValue::String("1.10"))
+		));
+	}
+
+	#[test]
+	fn test_get_save_meta_mod_names() {
+		let names = get_save_meta_mod_names(
+			r#"
+				version="Corvus v3.12.4"
+				flag={
+					icon={
+						category="pointy"
+						file="flag_pointy_1.dds"
+					}
+				}
+				mods={
+					"Better Flags"
+					"2.0"
+				}
+			"#,
+		)
+		.unwrap();
+		assert_eq!(names, vec!["Better Flags", "2.0"]);
+	}
+
+	#[test]
+	fn test_parse_descriptor_with_archive() {
+		let descriptor = ModDescriptor::parse(
+			r#"
+				name="Zipped"
+				archive="C:/Steam/steamapps/workshop/content/281990/123/mod.zip"
+			"#,
+		)
+		.unwrap();
+		assert_eq!(descriptor.path, None);
+		assert_eq!(
+			descriptor.archive,
+			Some(PathBuf::from(
+				"C:/Steam/steamapps/workshop/content/281990/123/mod.zip"
+			))
+		);
+	}
+
+	#[test]
+	fn test_launcher_playset_order() {
+		let user_data_dir = TempDir::new().unwrap();
+		let connection = create_launcher_db(user_data_dir.path());
+		write_descriptor(
+			user_data_dir.path(),
+			"mod/ugc_2.mod",
+			r#"name="Second" replace_path="flags/backgrounds""#,
+		);
+		connection
+			.execute_batch(
+				"INSERT INTO playsets VALUES ('inactive', 'Old', 0), ('active', 'Current', 1);
+				INSERT INTO mods VALUES
+					('m1', 'mod/ugc_1.mod', 'First', '/workshop/1', NULL),
+					('m2', 'mod/ugc_2.mod', 'Second (launcher name)', '/workshop/2', NULL),
+					('m3', 'mod/ugc_3.mod', 'Disabled', '/workshop/3', NULL),
+					('m4', 'mod/ugc_4.mod', 'Other Playset', '/workshop/4', NULL),
+					('m5', 'mod/ugc_5.mod', 'Zipped', NULL, '/workshop/5/mod.zip');
+				INSERT INTO playsets_mods VALUES
+					('active', 'm2', 1, 1),
+					('active', 'm1', 1, 0),
+					('active', 'm3', 0, 2),
+					('inactive', 'm4', 1, 3),
+					('active', 'm5', 1, 4);",
+			)
+			.unwrap();
+		let mods = get_enabled_mods(user_data_dir.path()).unwrap();
+		assert_eq!(names(&mods), vec!["First", "Second", "Zipped"]);
+		assert_eq!(mods[0].descriptor, Some(String::from("mod/ugc_1.mod")));
+		assert_eq!(mods[1].path, Some(PathBuf::from("/workshop/2")));
+		assert_eq!(
+			mods[1].replace_paths,
+			vec![PathBuf::from("flags/backgrounds")]
+		);
+		assert_eq!(mods[2].path, None);
+		assert_eq!(mods[2].archive, Some(PathBuf::from("/workshop/5/mod.zip")));
+	}
+
+	#[test]
+	fn test_falls_back_to_dlc_load_without_active_playset() {
+		let user_data_dir = TempDir::new().unwrap();
+		let connection = create_launcher_db(user_data_dir.path());
+		connection
+			.execute("INSERT INTO playsets VALUES ('p', 'Playset', 0)", [])
+			.unwrap();
+		write_descriptor(
+			user_data_dir.path(),
+			"dlc_load.json",
+			r#"{"enabled_mods":["mod/local.mod","mod/missing.mod"],"disabled_dlcs":[]}"#,
+		);
+		write_descriptor(
+			user_data_dir.path(),
+			"mod/local.mod",
+			r#"name="Local" path="mod/local""#,
+		);
+		let mods = get_enabled_mods(user_data_dir.path()).unwrap();
+		assert_eq!(names(&mods), vec!["Local"]);
+		assert_eq!(mods[0].path, Some(user_data_dir.path().join("mod/local")));
+	}
+
+	#[test]
+	fn test_resolve_save_mods() {
+		let user_data_dir = TempDir::new().unwrap();
+		let workshop_dir = TempDir::new().unwrap();
+		write_descriptor(
+			user_data_dir.path(),
+			"mod/ugc_111.mod",
+			r#"name="Named Mod" path="/workshop/111" remote_file_id="111""#,
+		);
+		write_descriptor(
+			user_data_dir.path(),
+			"mod/local.mod",
+			r#"name="Local Mod" path="mod/local""#,
+		);
+		write_descriptor(
+			workshop_dir.path(),
+			"222/descriptor.mod",
+			r#"name="Workshop Only""#,
+		);
+		write_descriptor(
+			workshop_dir.path(),
+			"333/descriptor.mod",
+			r#"name="Zipped" archive="zipped.zip""#,
+		);
+		let installed = get_installed_mods(user_data_dir.path(), &[workshop_dir.path().to_path_buf()]);
+		let save_mods = resolve_save_mods(
+			&[
+				String::from("222"),
+				String::from("mod/local.mod"),
+				String::from("Uninstalled Mod"),
+				String::from("Named Mod"),
+				String::from("Zipped"),
+			],
+			&installed,
+		);
+		assert_eq!(
+			names(&save_mods.resolved),
+			vec!["Workshop Only", "Local Mod", "Named Mod", "Zipped"]
+		);
+		assert_eq!(save_mods.missing, vec![String::from("Uninstalled Mod")]);
+		assert_eq!(
+			save_mods.resolved[0].path,
+			Some(workshop_dir.path().join("222"))
+		);
+		assert_eq!(
+			save_mods.resolved[1].path,
+			Some(user_data_dir.path().join("mod/local"))
+		);
+		assert_eq!(
+			save_mods.resolved[3].archive,
+			Some(workshop_dir.path().join("333").join("zipped.zip"))
+		);
+	}
+}
@@ -1,7 +1,6 @@
 use std::collections::HashMap;
 
 use crate::lexer::Token;
-use anyhow;
 use logos::{Lexer, Logos};
 use serde_json::{Map, Number, Value};
 
@@ -10,6 +9,22 @@ pub fn parse(string: &str, filter: &Value) -> anyhow::Result<Value> {
 	return parse_object(&mut lex, filter);
 }
 
+/// Strips the BOM and converts CRLF line endings, which many game and mod files on Windows have
+///
+/// The lexer only treats `\n` as whitespace, so a stray `\r` becomes a token and breaks parsing
+pub fn normalize_text(content: &str) -> String {
+	return content.trim_start_matches('\u{feff}').replace("\r\n", "\n");
+}
+
+/// All values for a key that may be repeated (see `$multiKeys`), in the order they appeared
+pub fn get_all<'a>(object: &'a Value, key: &str) -> Vec<&'a Value> {
+	let mut values: Vec<&Value> = object.get(key).into_iter().collect();
+	if let Some(Value::Array(multi_values)) = object.get("$multiKeys").and_then(|m| m.get(key)) {
+		values.extend(multi_values.iter());
+	}
+	return values;
+}
+
 fn parse_object<'source>(
 	lex: &mut Lexer<'source, Token<'source>>,
 	filter: &Value,
@@ -23,7 +38,7 @@ fn parse_object<'source>(
 	let mut value = None;
 
 	let mut token = lex.next();
-	while token != None && token != Some(Ok(Token::Close)) {
+	while token.is_some() && token != Some(Ok(Token::Close)) {
 		match token {
 			Some(Ok(Token::Text(s))) | Some(Ok(Token::String(s))) => {
 				if assigning {
@@ -159,7 +174,7 @@ fn skip_value<'source>(
 ) -> anyhow::Result<Option<&'source str>> {
 	let mut token = lex.next();
 	let mut depth: u64 = 0;
-	while token != None {
+	while token.is_some() {
 		match token {
 			Some(Ok(Token::Text(s))) | Some(Ok(Token::String(s))) => {
 				if depth == 0 {
@@ -222,9 +237,9 @@ fn get_next_filter<'a>(key: &String, filter: &'a Value) -> &'a Value {
 	} else if filter.is_object() {
 		let object = filter.as_object().unwrap();
 		if let Some(val) = object.get(key) {
-			return &val;
+			return val;
 		} else if let Some(val) = object.get("*") {
-			return &val;
+			return val;
 		} else {
 			return &Value::Bool(false);
 		}
@@ -233,13 +248,13 @@ fn get_next_filter<'a>(key: &String, filter: &'a Value) -> &'a Value {
 	};
 }
 
-fn get_next_filter_array<'a>(filter: &'a Value) -> &'a Value {
+fn get_next_filter_array(filter: &Value) -> &Value {
 	if filter.is_boolean() {
 		return filter;
 	} else if filter.is_array() {
 		let array = filter.as_array().unwrap();
-		if let Some(val) = array.get(0) {
-			return &val;
+		if let Some(val) = array.first() {
+			return val;
 		} else {
 			return &Value::Bool(false);
 		}
@@ -258,6 +273,14 @@ mod tests {
 		return parse(string, &Value::Bool(true));
 	}
 
+	#[test]
+	fn test_normalize_text() {
+		let content = "\u{feff}foo = bar\r\nbaz = {\r\n\tbax\r\n}\r\n";
+		assert!(parse_full(content).is_err());
+		let actual = parse_full(&normalize_text(content)).unwrap();
+		assert_json_eq!(actual, json!({ "foo": "bar", "baz": ["bax"] }));
+	}
+
 	#[test]
 	fn test_basic_map() {
 		let actual = parse_full("foo = bar").unwrap();
@@ -422,6 +445,17 @@ mod tests {
 		assert_json_eq!(actual, expected);
 	}
 
+	#[test]
+	fn test_get_all() {
+		let parsed = parse_full("foo = 1 bar = 2 foo = 3 foo = 4").unwrap();
+		assert_eq!(
+			get_all(&parsed, "foo"),
+			vec![&json!(1), &json!(3), &json!(4)]
+		);
+		assert_eq!(get_all(&parsed, "bar"), vec![&json!(2)]);
+		assert!(get_all(&parsed, "baz").is_empty());
+	}
+
 	#[test]
 	fn test_filter() {
 		let actual = parse(
@@ -0,0 +1,326 @@
+use crate::gamestate::{self, get_db, get_f64, get_i64, get_i64_list, get_list, get_name, get_str};
+use rusqlite::{params, Connection};
+use serde_json::{json, Value};
+use std::fs;
+use std::path::Path;
+
+/// Foreign keys are declared for documentation and query tools, but not enforced (see export),
+/// since saves can reference deleted objects
+const SCHEMA: &str = "
+	CREATE TABLE species (
+		id INTEGER PRIMARY KEY,
+		name TEXT,
+		class TEXT,
+		base_species_id INTEGER REFERENCES species(id)
+	);
+	CREATE TABLE federations (
+		id INTEGER PRIMARY KEY,
+		name TEXT,
+		type TEXT,
+		leader_id INTEGER REFERENCES countries(id)
+	);
+	CREATE TABLE countries (
+		id INTEGER PRIMARY KEY,
+		name TEXT,
+		type TEXT,
+		capital_planet_id INTEGER REFERENCES planets(id),
+		founder_species_id INTEGER REFERENCES species(id),
+		federation_id INTEGER REFERENCES federations(id),
+		overlord_id INTEGER REFERENCES countries(id),
+		military_power REAL,
+		economy_power REAL,
+		tech_power REAL
+	);
+	CREATE TABLE federation_members (
+		federation_id INTEGER NOT NULL REFERENCES federations(id),
+		country_id INTEGER NOT NULL REFERENCES countries(id),
+		is_associate INTEGER NOT NULL,
+		PRIMARY KEY (federation_id, country_id)
+	);
+	CREATE TABLE systems (
+		id INTEGER PRIMARY KEY,
+		name TEXT,
+		x REAL,
+		y REAL,
+		star_class TEXT,
+		owner_id INTEGER REFERENCES countries(id)
+	);
+	CREATE TABLE hyperlanes (
+		from_system_id INTEGER NOT NULL REFERENCES systems(id),
+		to_system_id INTEGER NOT NULL REFERENCES systems(id),
+		length REAL,
+		PRIMARY KEY (from_system_id, to_system_id)
+	);
+	CREATE TABLE planets (
+		id INTEGER PRIMARY KEY,
+		name TEXT,
+		planet_class TEXT,
+		size INTEGER,
+		system_id INTEGER REFERENCES systems(id),
+		owner_id INTEGER REFERENCES countries(id),
+		controller_id INTEGER REFERENCES countries(id)
+	);
+	CREATE TABLE pops (
+		planet_id INTEGER NOT NULL REFERENCES planets(id),
+		species_id INTEGER NOT NULL REFERENCES species(id),
+		num_pops INTEGER NOT NULL,
+		num_enslaved INTEGER NOT NULL,
+		PRIMARY KEY (planet_id, species_id)
+	);
+	CREATE TABLE fleets (
+		id INTEGER PRIMARY KEY,
+		name TEXT,
+		owner_id INTEGER REFERENCES countries(id),
+		system_id INTEGER REFERENCES systems(id),
+		is_station INTEGER NOT NULL,
+		num_ships INTEGER NOT NULL,
+		military_power REAL
+	);
+	CREATE TABLE wars (
+		id INTEGER PRIMARY KEY,
+		name TEXT,
+		start_date TEXT
+	);
+	CREATE TABLE war_participants (
+		war_id INTEGER NOT NULL REFERENCES wars(id),
+		country_id INTEGER NOT NULL REFERENCES countries(id),
+		side TEXT NOT NULL CHECK (side IN ('attacker', 'defender')),
+		PRIMARY KEY (war_id, country_id)
+	);
+";
+
+pub fn get_filter() -> Value {
+	return json!({
+		"galactic_object": true,
+		"starbase_mgr": true,
+		"ships": { "*": { "fleet": true } },
+		"fleet": true,
+		"country": true,
+		"planets": true,
+		"species_db": true,
+		"federation": true,
+		"war": true,
+	});
+}
+
+/// Writes the save to a new SQLite database at db_path, replacing any existing file
+pub fn export_to_file(state: &Value, db_path: &Path) -> anyhow::Result<()> {
+	if db_path.exists() {
+		fs::remove_file(db_path)?;
+	}
+	let mut conn = Connection::open(db_path)?;
+	return export(state, &mut conn);
+}
+
+pub fn export(state: &Value, conn: &mut Connection) -> anyhow::Result<()> {
+	// the bundled SQLite enforces foreign keys by default
+	conn.execute_batch("PRAGMA foreign_keys = OFF")?;
+	let tx = conn.transaction()?;
+	tx.execute_batch(SCHEMA)?;
+
+	{
+		let mut insert = tx.prepare("INSERT INTO species VALUES (?1, ?2, ?3, ?4)")?;
+		for (id, species) in get_db(state, &["species_db"]) {
+			insert.execute(params![
+				id,
+				get_name(species),
+				get_str(species, "class"),
+				get_i64(species, "base_ref"),
+			])?;
+		}
+	}
+
+	{
+		let mut insert = tx.prepare("INSERT INTO federations VALUES (?1, ?2, ?3, ?4)")?;
+		let mut insert_member =
+			tx.prepare("INSERT OR IGNORE INTO federation_members VALUES (?1, ?2, ?3)")?;
+		for (id, federation) in get_db(state, &["federation"]) {
+			let federation_type = federation
+				.get("federation_progress")
+				.and_then(|progress| get_str(progress, "federation_type"));
+			insert.execute(params![
+				id,
+				get_name(federation),
+				federation_type,
+				get_i64(federation, "leader"),
+			])?;
+			for member in get_i64_list(federation, "members") {
+				insert_member.execute(params![id, member, false])?;
+			}
+			for associate in get_i64_list(federation, "associates") {
+				insert_member.execute(params![id, associate, true])?;
+			}
+		}
+	}
+
+	{
+		let mut insert =
+			tx.prepare("INSERT INTO countries VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10)")?;
+		for (id, country) in get_db(state, &["country"]) {
+			insert.execute(params![
+				id,
+				get_name(country),
+				get_str(country, "type"),
+				get_i64(country, "capital"),
+				get_i64(country, "founder_species_ref"),
+				get_i64(country, "federation"),
+				get_i64(country, "overlord"),
+				get_f64(country, "military_power"),
+				get_f64(country, "economy_power"),
+				get_f64(country, "tech_power"),
+			])?;
+		}
+	}
+
+	{
+		let system_owners = gamestate::get_system_owners(state);
+		let mut insert = tx.prepare("INSERT INTO systems VALUES (?1, ?2, ?3, ?4, ?5, ?6)")?;
+		let mut insert_hyperlane =
+			tx.prepare("INSERT OR IGNORE INTO hyperlanes VALUES (?1, ?2, ?3)")?;
+		for (id, system) in get_db(state, &["galactic_object"]) {
+			let coordinate = system.get("coordinate");
+			insert.execute(params![
+				id,
+				get_name(system),
+				coordinate.and_then(|c| get_f64(c, "x")),
+				coordinate.and_then(|c| get_f64(c, "y")),
+				get_str(system, "star_class"),
+				system_owners.get(&id),
+			])?;
+			for hyperlane in get_list(system, "hyperlane") {
+				// hyperlanes are listed on both ends; store each once, from the lower id
+				match get_i64(hyperlane, "to") {
+					Some(to) if to > id => {
+						insert_hyperlane.execute(params![id, to, get_f64(hyperlane, "length")])?;
+					}
+					_ => (),
+				}
+			}
+		}
+	}
+
+	{
+		let planet_systems = gamestate::get_planet_systems(state);
+		let mut insert = tx.prepare("INSERT INTO planets VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)")?;
+		let mut insert_pops = tx.prepare("INSERT INTO pops VALUES (?1, ?2, ?3, ?4)")?;
+		for (id, planet) in get_db(state, &["planets", "planet"]) {
+			let system_id = planet_systems
+				.get(&id)
+				.copied()
+				.or_else(|| gamestate::get_origin(planet.get("coordinate")));
+			insert.execute(params![
+				id,
+				get_name(planet),
+				get_str(planet, "planet_class"),
+				get_i64(planet, "planet_size"),
+				system_id,
+				get_i64(planet, "owner"),
+				get_i64(planet, "controller"),
+			])?;
+			if let Some(Value::Object(species_information)) = planet.get("species_information") {
+				for (species_id, info) in species_information {
+					insert_pops.execute(params![
+						id,
+						species_id.parse::<i64>()?,
+						get_i64(info, "num_pops").unwrap_or(0),
+						get_i64(info, "num_enslaved").unwrap_or(0),
+					])?;
+				}
+			}
+		}
+	}
+
+	{
+		let fleet_owners = gamestate::get_fleet_owners(state);
+		let mut insert = tx.prepare("INSERT INTO fleets VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)")?;
+		for (id, fleet) in get_db(state, &["fleet"]) {
+			let system_id = fleet
+				.get("movement_manager")
+				.and_then(|manager| gamestate::get_origin(manager.get("coordinate")));
+			insert.execute(params![
+				id,
+				get_name(fleet),
+				fleet_owners.get(&id),
+				system_id,
+				fleet
+					.get("station")
+					.and_then(Value::as_bool)
+					.unwrap_or(false),
+				get_i64_list(fleet, "ships").len() as i64,
+				get_f64(fleet, "military_power"),
+			])?;
+		}
+	}
+
+	{
+		let mut insert = tx.prepare("INSERT INTO wars VALUES (?1, ?2, ?3)")?;
+		let mut insert_participant =
+			tx.prepare("INSERT OR IGNORE INTO war_participants VALUES (?1, ?2, ?3)")?;
+		for (id, war) in get_db(state, &["war"]) {
+			insert.execute(params![id, get_name(war), get_str(war, "start_date")])?;
+			for side in ["attacker", "defender"] {
+				for participant in get_list(war, &format!("{}s", side)) {
+					if let Some(country) = get_i64(participant, "country") {
+						insert_participant.execute(params![id, country, side])?;
+					}
+				}
+			}
+		}
+	}
+
+	tx.commit()?;
+	return Ok(());
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::gamestate::tests::sample_state;
+
+	fn query_i64(conn: &Connection, sql: &str) -> i64 {
+		return conn.query_row(sql, [], |row| row.get(0)).unwrap();
+	}
+
+	#[test]
+	fn test_export() {
+		let mut conn = Connection::open_in_memory().unwrap();
+		export(&sample_state(), &mut conn).unwrap();
+
+		assert_eq!(query_i64(&conn, "SELECT COUNT(*) FROM systems"), 3);
+		assert_eq!(query_i64(&conn, "SELECT COUNT(*) FROM hyperlanes"), 2);
+		assert_eq!(
+			query_i64(&conn, "SELECT COUNT(*) FROM systems WHERE owner_id = 0"),
+			2
+		);
+		assert_eq!(
+			query_i64(&conn, "SELECT system_id FROM planets WHERE id = 101"),
+			0
+		);
+		assert_eq!(
+			query_i64(&conn, "SELECT owner_id FROM fleets WHERE id = 503"),
+			0
+		);
+		assert_eq!(
+			query_i64(
+				&conn,
+				"SELECT COUNT(*) FROM war_participants WHERE side = 'defender'"
+			),
+			1
+		);
+		// pops per species for the country with the most pops
+		let (species, pops): (String, i64) = conn
+			.query_row(
+				"SELECT species.name, SUM(pops.num_pops) AS total
+				FROM pops
+				JOIN planets ON planets.id = pops.planet_id
+				JOIN species ON species.id = pops.species_id
+				WHERE planets.owner_id = 0
+				GROUP BY species.id
+				ORDER BY total DESC",
+				[],
+				|row| Ok((row.get(0)?, row.get(1)?)),
+			)
+			.unwrap();
+		assert_eq!((species.as_str(), pops), ("Human", 40));
+	}
+}
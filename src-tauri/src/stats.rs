@@ -0,0 +1,185 @@
+use crate::campaign;
+use crate::gamestate::{self, get_db, get_f64, get_i64, get_name, get_str};
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+#[derive(serde::Serialize, PartialEq, Debug)]
+pub struct CountryStats {
+	/// The game date of the save, so rows from a whole campaign can be charted over time
+	pub date: String,
+	pub country_id: i64,
+	pub name: Option<String>,
+	#[serde(rename = "type")]
+	pub country_type: Option<String>,
+	pub systems: usize,
+	pub planets: usize,
+	pub pops: i64,
+	pub military_power: Option<f64>,
+	pub economy_power: Option<f64>,
+	pub tech_power: Option<f64>,
+	pub federation_id: Option<i64>,
+	pub federation: Option<String>,
+}
+
+pub fn get_filter() -> Value {
+	return json!({
+		"date": true,
+		"galactic_object": true,
+		"starbase_mgr": true,
+		"ships": { "*": { "fleet": true } },
+		"country": { "*": {
+			"name": true,
+			"type": true,
+			"federation": true,
+			"military_power": true,
+			"economy_power": true,
+			"tech_power": true,
+			"fleets_manager": true,
+		} },
+		"planets": { "planet": { "*": {
+			"owner": true,
+			"species_information": true,
+		} } },
+		"federation": { "*": { "name": true } },
+	});
+}
+
+/// One row per country that owns at least one system or planet, which skips the many
+/// internal countries (eg for event fleets) the game creates
+pub fn get_country_stats(state: &Value) -> Vec<CountryStats> {
+	let date = get_str(state, "date").unwrap_or_default().to_string();
+
+	let mut systems: HashMap<i64, usize> = HashMap::new();
+	for owner in gamestate::get_system_owners(state).into_values() {
+		*systems.entry(owner).or_default() += 1;
+	}
+
+	let mut planets: HashMap<i64, usize> = HashMap::new();
+	let mut pops: HashMap<i64, i64> = HashMap::new();
+	for (_, planet) in get_db(state, &["planets", "planet"]) {
+		let owner = match get_i64(planet, "owner") {
+			Some(owner) => owner,
+			None => continue,
+		};
+		*planets.entry(owner).or_default() += 1;
+		if let Some(Value::Object(species_information)) = planet.get("species_information") {
+			*pops.entry(owner).or_default() += species_information
+				.values()
+				.filter_map(|info| get_i64(info, "num_pops"))
+				.sum::<i64>();
+		}
+	}
+
+	let federations: HashMap<i64, Option<String>> = get_db(state, &["federation"])
+		.into_iter()
+		.map(|(id, federation)| (id, get_name(federation)))
+		.collect();
+
+	return get_db(state, &["country"])
+		.into_iter()
+		.filter(|(id, _)| systems.contains_key(id) || planets.contains_key(id))
+		.map(|(id, country)| {
+			let federation_id = get_i64(country, "federation");
+			CountryStats {
+				date: date.clone(),
+				country_id: id,
+				name: get_name(country),
+				country_type: get_str(country, "type").map(String::from),
+				systems: systems.get(&id).copied().unwrap_or(0),
+				planets: planets.get(&id).copied().unwrap_or(0),
+				pops: pops.get(&id).copied().unwrap_or(0),
+				military_power: get_f64(country, "military_power"),
+				economy_power: get_f64(country, "economy_power"),
+				tech_power: get_f64(country, "tech_power"),
+				federation_id,
+				federation: federation_id
+					.and_then(|federation_id| federations.get(&federation_id).cloned().flatten()),
+			}
+		})
+		.collect();
+}
+
+/// The stats of every save in a campaign dir, one row per country per save
+pub fn get_campaign_stats(dir: &PathBuf) -> anyhow::Result<Vec<CountryStats>> {
+	return Ok(
+		campaign::map_campaign_saves(dir, &get_filter(), get_country_stats)?
+			.into_iter()
+			.flat_map(|(_, rows)| rows)
+			.collect(),
+	);
+}
+
+/// Writes the rows as CSV, or as TSV if the path ends in .tsv
+pub fn export_to_file<T: serde::Serialize>(rows: &[T], path: &Path) -> anyhow::Result<()> {
+	let is_tsv = path
+		.extension()
+		.is_some_and(|ext| ext.eq_ignore_ascii_case("tsv"));
+	write_rows(
+		rows,
+		fs::File::create(path)?,
+		if is_tsv { b'\t' } else { b',' },
+	)?;
+	return Ok(());
+}
+
+pub fn write_rows<T: serde::Serialize, W: io::Write>(
+	rows: &[T],
+	writer: W,
+	delimiter: u8,
+) -> anyhow::Result<()> {
+	let mut writer = csv::WriterBuilder::new()
+		.delimiter(delimiter)
+		.from_writer(writer);
+	for row in rows {
+		writer.serialize(row)?;
+	}
+	writer.flush()?;
+	return Ok(());
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::gamestate::tests::sample_state;
+
+	#[test]
+	fn test_get_country_stats() {
+		let stats = get_country_stats(&sample_state());
+		assert_eq!(stats.len(), 2);
+		assert_eq!(
+			stats[0],
+			CountryStats {
+				date: String::from("2250.06.01"),
+				country_id: 0,
+				name: Some(String::from("United Nations of Earth")),
+				country_type: Some(String::from("default")),
+				systems: 2,
+				planets: 1,
+				pops: 42,
+				military_power: Some(1350.5),
+				economy_power: Some(300.0),
+				tech_power: Some(200.0),
+				federation_id: Some(0),
+				federation: Some(String::from("Galactic Union")),
+			}
+		);
+		assert_eq!(
+			(stats[1].systems, stats[1].pops, stats[1].federation_id),
+			(1, 10, None)
+		);
+	}
+
+	#[test]
+	fn test_write_rows() {
+		let mut output = vec![];
+		write_rows(&get_country_stats(&sample_state()), &mut output, b'\t').unwrap();
+		let output = String::from_utf8(output).unwrap();
+		let lines: Vec<&str> = output.lines().collect();
+		assert_eq!(lines.len(), 3);
+		assert!(lines[0].starts_with("date\tcountry_id\tname\ttype\tsystems\t"));
+		assert!(lines[2].starts_with("2250.06.01\t1\tSirian Hegemony\tdefault\t1\t1\t10\t"));
+	}
+}
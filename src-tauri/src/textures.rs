@@ -0,0 +1,232 @@
+use crate::emblems;
+use crate::parser;
+use crate::vfs::{DataFile, DataFs, FileFilter};
+use image::RgbaImage;
+use serde_json::{json, Value};
+use std::ffi::OsString;
+use std::path::{Path, PathBuf};
+
+#[derive(serde::Deserialize, Clone, Copy, PartialEq, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum TextureKind {
+	/// A planet_class from the save, eg "pc_desert"
+	PlanetClass,
+	/// A galactic_object's star_class, eg "sc_b"
+	StarClass,
+	/// A species' portrait, eg "hum1"; only portraits defined with a 2D texturefile are supported
+	Portrait,
+}
+
+/// Finds the texture file for a planet class, star class, or portrait, after mod overrides
+pub fn resolve_texture(data_fs: &DataFs, kind: TextureKind, key: &str) -> anyhow::Result<DataFile> {
+	let texture_path = match kind {
+		TextureKind::PlanetClass => {
+			let sprite = get_planet_class_sprite(data_fs, key)?;
+			get_sprite_texture_path(data_fs, &sprite)?
+		}
+		TextureKind::StarClass => {
+			// stars are drawn as planets of the star class's planet class, eg sc_b uses pc_b_star
+			let star_class = get_definition(data_fs, Path::new("common/star_classes"), key)
+				.ok_or(anyhow::anyhow!("Star class {} not found", key))?;
+			let planet_class = star_class
+				.get("planet")
+				.and_then(get_first_key)
+				.ok_or(anyhow::anyhow!("Star class {} has no planet class", key))?;
+			let sprite = get_planet_class_sprite(data_fs, &planet_class)?;
+			get_sprite_texture_path(data_fs, &sprite)?
+		}
+		TextureKind::Portrait => {
+			let portraits = get_definition(data_fs, Path::new("gfx/portraits/portraits"), "portraits")
+				.ok_or(anyhow::anyhow!("No portraits defined"))?;
+			let texture_file = portraits
+				.get(key)
+				.and_then(|portrait| portrait.get("texturefile"))
+				.and_then(Value::as_str)
+				.ok_or(anyhow::anyhow!("Portrait {} has no 2D texture", key))?;
+			normalize_texture_path(texture_file)
+		}
+	};
+	return data_fs.resolve(&texture_path).ok_or(anyhow::anyhow!(
+		"No data dir contained texture {}",
+		texture_path.display()
+	));
+}
+
+/// Most textures are DDS, but mods sometimes use PNG or TGA
+pub fn decode_texture(file: &DataFile, size: Option<u32>) -> anyhow::Result<RgbaImage> {
+	let is_dds = file
+		.relative_path
+		.extension()
+		.is_some_and(|ext| ext.eq_ignore_ascii_case("dds"));
+	if is_dds {
+		return match size {
+			Some(size) => emblems::decode_dds_at_size(file, size),
+			None => emblems::decode_dds(file),
+		};
+	}
+	let img = image::load_from_memory(&file.read()?)?.to_rgba8();
+	return Ok(match size {
+		Some(size) => emblems::resize_to_fit(&img, size),
+		None => img,
+	});
+}
+
+fn get_planet_class_sprite(data_fs: &DataFs, planet_class: &str) -> anyhow::Result<String> {
+	let definition = get_definition(data_fs, Path::new("common/planet_classes"), planet_class)
+		.ok_or(anyhow::anyhow!("Planet class {} not found", planet_class))?;
+	return definition
+		.get("icon")
+		.and_then(Value::as_str)
+		.map(String::from)
+		.ok_or(anyhow::anyhow!("Planet class {} has no icon", planet_class));
+}
+
+/// The last definition of a top-level key in the dir's .txt files, like the game's load order
+///
+/// Only files mentioning the key are parsed, since most of common/ is irrelevant to any one lookup
+fn get_definition(data_fs: &DataFs, dir: &Path, key: &str) -> Option<Value> {
+	let filter = json!({ key: true });
+	let mut definition = None;
+	for file in data_fs.get_files(dir, &FileFilter::Extension(OsString::from("txt")), 1) {
+		let content = match file.read_to_string() {
+			Ok(content) if content.contains(key) => content,
+			_ => continue,
+		};
+		match parser::parse(&parser::normalize_text(&content), &filter) {
+			Ok(parsed) => {
+				if let Some(value) = parser::get_all(&parsed, key).pop() {
+					definition = Some(value.clone());
+				}
+			}
+			Err(err) => println!("failed to parse {}: {}", file.path().display(), err),
+		}
+	}
+	return definition;
+}
+
+/// Resolves a sprite name (eg "GFX_planet_type_desert") through the spriteTypes in interface/*.gfx
+fn get_sprite_texture_path(data_fs: &DataFs, sprite: &str) -> anyhow::Result<PathBuf> {
+	let filter = json!({ "spriteTypes": { "spriteType": true } });
+	let mut texture_path = None;
+	for file in data_fs.get_files(
+		Path::new("interface"),
+		&FileFilter::Extension(OsString::from("gfx")),
+		2,
+	) {
+		let content = match file.read_to_string() {
+			Ok(content) if content.contains(sprite) => content,
+			_ => continue,
+		};
+		let parsed = match parser::parse(&parser::normalize_text(&content), &filter) {
+			Ok(parsed) => parsed,
+			Err(err) => {
+				println!("failed to parse {}: {}", file.path().display(), err);
+				continue;
+			}
+		};
+		for sprite_types in parser::get_all(&parsed, "spriteTypes") {
+			for sprite_type in parser::get_all(sprite_types, "spriteType") {
+				if sprite_type.get("name").and_then(Value::as_str) == Some(sprite) {
+					if let Some(texture_file) = sprite_type.get("texturefile").and_then(Value::as_str) {
+						texture_path = Some(normalize_texture_path(texture_file));
+					}
+				}
+			}
+		}
+	}
+	return texture_path.ok_or(anyhow::anyhow!("Sprite {} not found", sprite));
+}
+
+/// Texture paths are relative to the data root and sometimes written with backslashes
+fn normalize_texture_path(texture_file: &str) -> PathBuf {
+	return texture_file
+		.split(['/', '\\'])
+		.filter(|part| !part.is_empty())
+		.collect();
+}
+
+/// Accepts `planet = pc_b_star`, `planet = { key = pc_b_star }` and `planet = { pc_b_star }`
+fn get_first_key(value: &Value) -> Option<String> {
+	return match value {
+		Value::String(key) => Some(key.clone()),
+		Value::Array(keys) => keys.first().and_then(get_first_key),
+		Value::Object(_) => value.get("key").and_then(get_first_key),
+		_ => None,
+	};
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::emblems::tests::write_dds;
+	use crate::vfs::DataLayer;
+	use std::fs;
+	use tempfile::TempDir;
+
+	fn write_file(root: &Path, path: &str, content: &str) {
+		let path = root.join(path);
+		fs::create_dir_all(path.parent().unwrap()).unwrap();
+		fs::write(path, content).unwrap();
+	}
+
+	#[test]
+	fn test_resolve_planet_and_star_textures() {
+		let vanilla = TempDir::new().unwrap();
+		let mod_dir = TempDir::new().unwrap();
+		write_file(
+			vanilla.path(),
+			"common/planet_classes/00_planet_classes.txt",
+			"pc_desert = { icon = GFX_planet_type_desert }\r\npc_b_star = { icon = GFX_planet_type_b_star }\r\n",
+		);
+		write_file(
+			vanilla.path(),
+			"common/star_classes/00_star_classes.txt",
+			"sc_b = { class = b_star planet = { key = pc_b_star } }",
+		);
+		write_file(
+			vanilla.path(),
+			"interface/planet_types.gfx",
+			// vanilla's interface files have a BOM and CRLF line endings
+			"\u{feff}spriteTypes = {\r\n\
+				spriteType = { name = \"GFX_planet_type_desert\" texturefile = \"gfx\\interface\\planets\\desert.dds\" }\r\n\
+				spriteType = { name = \"GFX_planet_type_b_star\" texturefile = \"gfx/interface/planets/b_star.dds\" }\r\n\
+			}\r\n",
+		);
+		write_dds(
+			&vanilla.path().join("gfx/interface/planets/desert.dds"),
+			&RgbaImage::new(8, 8),
+		);
+		write_dds(
+			&vanilla.path().join("gfx/interface/planets/b_star.dds"),
+			&RgbaImage::new(8, 8),
+		);
+		// the mod overrides the desert texture without touching the sprite definitions
+		write_dds(
+			&mod_dir.path().join("gfx/interface/planets/desert.dds"),
+			&RgbaImage::new(4, 4),
+		);
+		let data_fs = DataFs::new(vec![
+			DataLayer::new(vanilla.path().to_path_buf(), vec![]),
+			DataLayer::new(mod_dir.path().to_path_buf(), vec![]),
+		]);
+
+		let desert = resolve_texture(&data_fs, TextureKind::PlanetClass, "pc_desert").unwrap();
+		assert_eq!(desert.root, mod_dir.path());
+		assert_eq!(
+			desert.relative_path,
+			PathBuf::from("gfx/interface/planets/desert.dds")
+		);
+		assert_eq!(
+			decode_texture(&desert, Some(2)).unwrap().dimensions(),
+			(2, 2)
+		);
+
+		let star = resolve_texture(&data_fs, TextureKind::StarClass, "sc_b").unwrap();
+		assert_eq!(
+			star.relative_path,
+			PathBuf::from("gfx/interface/planets/b_star.dds")
+		);
+
+		assert!(resolve_texture(&data_fs, TextureKind::PlanetClass, "pc_missing").is_err());
+	}
+}
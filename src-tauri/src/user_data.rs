@@ -0,0 +1,242 @@
+use crate::config;
+use crate::install::STEAM_APP_ID;
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use steamlocate::SteamDir;
+
+pub const USER_DATA_DIR_ENV_VAR: &str = "STELLARIS_USER_DATA_DIR";
+/// A list of dirs, separated like PATH (":" on unix, ";" on windows)
+pub const SAVE_DIRS_ENV_VAR: &str = "STELLARIS_SAVE_DIRS";
+
+#[derive(serde::Serialize, Clone, Copy, PartialEq, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum RootKind {
+	UserData,
+	Saves,
+}
+
+#[derive(serde::Serialize)]
+pub struct SearchedRoot {
+	pub kind: RootKind,
+	pub path: PathBuf,
+	/// Where this root came from, eg "default Linux path"
+	pub reason: String,
+	pub exists: bool,
+	/// Short descriptions of what's there, eg "dlc_load.json" or "3 campaigns, 41 saves"
+	pub found: Vec<String>,
+}
+
+/// Candidate user data dirs in priority order, whether or not they exist
+pub fn get_user_data_dir_candidates() -> Vec<(PathBuf, String)> {
+	let mut candidates = vec![];
+	if let Some(path) = env::var_os(USER_DATA_DIR_ENV_VAR).filter(|p| !p.is_empty()) {
+		candidates.push((
+			PathBuf::from(path),
+			format!("{} environment variable", USER_DATA_DIR_ENV_VAR),
+		));
+	}
+	if let Some(path) = config::load_config().user_data_path {
+		candidates.push((path, String::from("config file")));
+	}
+	let default_dir = match env::consts::OS {
+		"linux" => dirs::home_dir().map(|home| home.join(".local/share/Paradox Interactive/Stellaris")),
+		"macos" | "windows" => {
+			dirs::document_dir().map(|docs| docs.join("Paradox Interactive").join("Stellaris"))
+		}
+		_ => None,
+	};
+	if let Some(path) = default_dir {
+		candidates.push((path, format!("default {} path", env::consts::OS)));
+	}
+	if env::consts::OS == "linux" {
+		for path in get_proton_user_data_dirs() {
+			candidates.push((path, String::from("Proton prefix")));
+		}
+	}
+	return dedup(candidates);
+}
+
+/// The first candidate that exists
+pub fn get_user_data_dir() -> anyhow::Result<PathBuf> {
+	return get_user_data_dir_candidates()
+		.into_iter()
+		.map(|(path, _)| path)
+		.find(|path| path.is_dir())
+		.ok_or(anyhow::anyhow!("Stellaris user data dir not found"));
+}
+
+/// Dirs containing one sub-dir per campaign, whether or not they exist
+///
+/// Unlike the user data dir, all of these are used, since Steam Cloud and local saves live side-by-side
+pub fn get_save_dir_candidates() -> Vec<(PathBuf, String)> {
+	let mut candidates = vec![];
+	if let Some(paths) = env::var_os(SAVE_DIRS_ENV_VAR) {
+		for path in env::split_paths(&paths).filter(|p| !p.as_os_str().is_empty()) {
+			candidates.push((path, format!("{} environment variable", SAVE_DIRS_ENV_VAR)));
+		}
+	}
+	for path in config::load_config().save_dirs {
+		candidates.push((path, String::from("config file")));
+	}
+	for path in get_steam_cloud_save_dirs() {
+		candidates.push((path, String::from("Steam Cloud")));
+	}
+	for (path, reason) in get_user_data_dir_candidates() {
+		candidates.push((path.join("save games"), reason));
+	}
+	return dedup(candidates);
+}
+
+/// Every root we look in, with a summary of its contents, for troubleshooting missing saves or mods
+pub fn search_roots() -> Vec<SearchedRoot> {
+	let user_data_roots = get_user_data_dir_candidates()
+		.into_iter()
+		.map(|(path, reason)| search_root(RootKind::UserData, path, reason));
+	let save_roots = get_save_dir_candidates()
+		.into_iter()
+		.map(|(path, reason)| search_root(RootKind::Saves, path, reason));
+	return user_data_roots.chain(save_roots).collect();
+}
+
+fn search_root(kind: RootKind, path: PathBuf, reason: String) -> SearchedRoot {
+	let mut found = vec![];
+	if path.is_dir() {
+		match kind {
+			RootKind::UserData => {
+				for file in ["dlc_load.json", "launcher-v2.sqlite"] {
+					if path.join(file).is_file() {
+						found.push(file.to_string());
+					}
+				}
+				let descriptors = count_entries(&path.join("mod"), |p| {
+					p.extension().is_some_and(|ext| ext == "mod")
+				});
+				if descriptors > 0 {
+					found.push(format!("{} mod descriptors", descriptors));
+				}
+			}
+			RootKind::Saves => {
+				let campaign_dirs = get_sub_dirs(&path);
+				let saves: usize = campaign_dirs
+					.iter()
+					.map(|dir| count_entries(dir, |p| p.extension().is_some_and(|ext| ext == "sav")))
+					.sum();
+				found.push(format!(
+					"{} campaigns, {} saves",
+					campaign_dirs.len(),
+					saves
+				));
+			}
+		}
+	}
+	return SearchedRoot {
+		kind,
+		exists: path.is_dir(),
+		path,
+		reason,
+		found,
+	};
+}
+
+/// Missing or unreadable dirs have no sub-dirs
+pub fn get_sub_dirs(path: &Path) -> Vec<PathBuf> {
+	return fs::read_dir(path)
+		.into_iter()
+		.flatten()
+		.flatten()
+		.map(|entry| entry.path())
+		.filter(|path| path.is_dir())
+		.collect();
+}
+
+fn count_entries(path: &Path, predicate: impl Fn(&Path) -> bool) -> usize {
+	return fs::read_dir(path)
+		.into_iter()
+		.flatten()
+		.flatten()
+		.filter(|entry| predicate(&entry.path()))
+		.count();
+}
+
+fn get_steam_cloud_save_dirs() -> Vec<PathBuf> {
+	let steam_user_data_dir = match SteamDir::locate() {
+		Ok(steam_dir) => steam_dir.path().join("userdata"),
+		Err(_) => return vec![],
+	};
+	return get_sub_dirs(&steam_user_data_dir)
+		.into_iter()
+		.map(|dir| {
+			dir
+				.join(STEAM_APP_ID.to_string())
+				.join("remote")
+				.join("save games")
+		})
+		.collect();
+}
+
+/// When the Windows version runs under Proton, its Documents folder lives in the Steam library's compatdata
+fn get_proton_user_data_dirs() -> Vec<PathBuf> {
+	let library_paths = SteamDir::locate()
+		.and_then(|steam_dir| steam_dir.library_paths())
+		.unwrap_or_default();
+	return library_paths
+		.into_iter()
+		.map(|library| {
+			library
+				.join("steamapps/compatdata")
+				.join(STEAM_APP_ID.to_string())
+				.join("pfx/drive_c/users/steamuser/Documents/Paradox Interactive/Stellaris")
+		})
+		.filter(|path| path.is_dir())
+		.collect();
+}
+
+fn dedup(candidates: Vec<(PathBuf, String)>) -> Vec<(PathBuf, String)> {
+	let mut deduped: Vec<(PathBuf, String)> = vec![];
+	for (path, reason) in candidates {
+		let canonical = fs::canonicalize(&path).unwrap_or(path.clone());
+		if !deduped
+			.iter()
+			.any(|(p, _)| fs::canonicalize(p).unwrap_or(p.clone()) == canonical)
+		{
+			deduped.push((path, reason));
+		}
+	}
+	return deduped;
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use tempfile::TempDir;
+
+	#[test]
+	fn test_search_save_root() {
+		let dir = TempDir::new().unwrap();
+		for file in [
+			"campaign1/autosave.sav",
+			"campaign1/quick.sav",
+			"campaign2/x.sav",
+		] {
+			let path = dir.path().join(file);
+			fs::create_dir_all(path.parent().unwrap()).unwrap();
+			fs::write(path, "").unwrap();
+		}
+		let root = search_root(RootKind::Saves, dir.path().to_path_buf(), String::new());
+		assert!(root.exists);
+		assert_eq!(root.found, vec![String::from("2 campaigns, 3 saves")]);
+	}
+
+	#[test]
+	fn test_search_missing_root() {
+		let dir = TempDir::new().unwrap();
+		let root = search_root(
+			RootKind::UserData,
+			dir.path().join("missing"),
+			String::new(),
+		);
+		assert!(!root.exists);
+		assert!(root.found.is_empty());
+	}
+}
@@ -0,0 +1,373 @@
+use std::collections::{HashMap, HashSet};
+use std::ffi::OsString;
+use std::fs;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::SystemTime;
+
+pub enum FileFilter {
+	Extension(OsString),
+}
+
+pub fn get_files_matching_filter(
+	path: &PathBuf,
+	filter: &FileFilter,
+	depth: u8,
+) -> anyhow::Result<Vec<PathBuf>> {
+	let mut files: Vec<PathBuf> = Vec::new();
+	if path.is_dir() {
+		for entry in fs::read_dir(path)? {
+			let entry = entry?;
+			let path = entry.path();
+			if path.is_dir() && (depth > 1 || depth == 0) {
+				let mut sub_dir_files = get_files_matching_filter(&path, filter, depth - 1)?;
+				files.append(&mut sub_dir_files);
+			} else {
+				match filter {
+					FileFilter::Extension(filter_ext) => match path.extension() {
+						Some(ext) if ext == filter_ext => files.push(path),
+						_ => (),
+					},
+				}
+			}
+		}
+	}
+	Ok(files)
+}
+
+/// One root of game data, eg the install dir, a mod dir, or a zipped mod
+pub struct DataLayer {
+	/// The directory, or the zip file for archived mods
+	pub root: PathBuf,
+	/// Directories (relative to the data root) whose files from earlier layers are ignored
+	pub replace_paths: Vec<PathBuf>,
+	/// For archived mods, the relative paths of all files in the zip
+	archive_entries: Option<HashSet<PathBuf>>,
+}
+
+impl DataLayer {
+	pub fn new(root: PathBuf, replace_paths: Vec<PathBuf>) -> Self {
+		return DataLayer {
+			root,
+			replace_paths,
+			archive_entries: None,
+		};
+	}
+
+	/// A mod distributed as a zip (descriptor `archive=` instead of `path=`)
+	pub fn from_archive(archive: PathBuf, replace_paths: Vec<PathBuf>) -> anyhow::Result<Self> {
+		let zip = zip::ZipArchive::new(fs::File::open(&archive)?)?;
+		let archive_entries = zip
+			.file_names()
+			.filter(|name| !name.ends_with('/'))
+			.map(PathBuf::from)
+			.collect();
+		return Ok(DataLayer {
+			root: archive,
+			replace_paths,
+			archive_entries: Some(archive_entries),
+		});
+	}
+
+	/// replace_path only applies to files directly in the directory, not its sub-directories
+	fn replaces(&self, relative_path: &Path) -> bool {
+		return match relative_path.parent() {
+			Some(parent) => self.replace_paths.iter().any(|p| p == parent),
+			None => false,
+		};
+	}
+
+	fn has_file(&self, relative_path: &Path) -> bool {
+		return match &self.archive_entries {
+			Some(entries) => entries.contains(relative_path),
+			None => self.root.join(relative_path).is_file(),
+		};
+	}
+
+	/// Relative paths of files in relative_dir, with the same depth semantics as get_files_matching_filter
+	fn get_files(&self, relative_dir: &Path, filter: &FileFilter, depth: u8) -> Vec<PathBuf> {
+		match &self.archive_entries {
+			Some(entries) => {
+				return entries
+					.iter()
+					.filter(|entry| match entry.strip_prefix(relative_dir) {
+						Ok(rest) => depth == 0 || rest.components().count() <= depth as usize,
+						Err(_) => false,
+					})
+					.filter(|entry| match filter {
+						FileFilter::Extension(filter_ext) => entry.extension() == Some(filter_ext),
+					})
+					.cloned()
+					.collect();
+			}
+			None => {
+				let dir = self.root.join(relative_dir);
+				return get_files_matching_filter(&dir, filter, depth)
+					.unwrap_or_default()
+					.into_iter()
+					.map(|file| {
+						file
+							.strip_prefix(&self.root)
+							.expect("data file is not descendant of data root dir")
+							.to_path_buf()
+					})
+					.collect();
+			}
+		}
+	}
+
+	fn get_file(&self, relative_path: &Path) -> DataFile {
+		return DataFile {
+			root: self.root.clone(),
+			relative_path: relative_path.to_path_buf(),
+			in_archive: self.archive_entries.is_some(),
+		};
+	}
+}
+
+/// A data file, after mod overrides have been applied
+#[derive(Debug, PartialEq)]
+pub struct DataFile {
+	pub root: PathBuf,
+	pub relative_path: PathBuf,
+	/// If true, root is a zip file and relative_path is an entry in it
+	pub in_archive: bool,
+}
+
+impl DataFile {
+	/// The path on disk, or for archived files a display path within the zip
+	pub fn path(&self) -> PathBuf {
+		return self.root.join(&self.relative_path);
+	}
+
+	pub fn read(&self) -> anyhow::Result<Vec<u8>> {
+		if self.in_archive {
+			let mut zip = zip::ZipArchive::new(fs::File::open(&self.root)?)?;
+			// zip entry names always use forward slashes
+			let name = self
+				.relative_path
+				.components()
+				.map(|c| c.as_os_str().to_string_lossy())
+				.collect::<Vec<_>>()
+				.join("/");
+			let mut bytes = vec![];
+			zip.by_name(&name)?.read_to_end(&mut bytes)?;
+			return Ok(bytes);
+		} else {
+			return Ok(fs::read(self.path())?);
+		}
+	}
+
+	pub fn read_to_string(&self) -> anyhow::Result<String> {
+		return Ok(String::from_utf8(self.read()?)?);
+	}
+
+	/// For archived files, the zip's modification time
+	pub fn modified(&self) -> anyhow::Result<SystemTime> {
+		let path = if self.in_archive {
+			self.root.clone()
+		} else {
+			self.path()
+		};
+		return Ok(fs::metadata(path)?.modified()?);
+	}
+}
+
+/// Layers vanilla and mod data dirs in load order, the same way the game does:
+/// - a file in a later layer overrides the file at the same relative path in earlier layers
+/// - a layer's replace_path hides every file from earlier layers in that directory
+pub struct DataFs {
+	layers: Vec<Arc<DataLayer>>,
+}
+
+impl DataFs {
+	/// Layers can be shared between data fs (eg the cached mod layers of a save) by passing them as Arcs
+	pub fn new(layers: impl IntoIterator<Item = impl Into<Arc<DataLayer>>>) -> Self {
+		return DataFs {
+			layers: layers.into_iter().map(Into::into).collect(),
+		};
+	}
+
+	/// All files in relative_dir (recursing up to depth), sorted by relative path
+	pub fn get_files(&self, relative_dir: &Path, filter: &FileFilter, depth: u8) -> Vec<DataFile> {
+		let mut relative_path_to_layer: HashMap<PathBuf, usize> = HashMap::new();
+		for (index, layer) in self.layers.iter().enumerate() {
+			if !layer.replace_paths.is_empty() {
+				relative_path_to_layer.retain(|relative_path, _| !layer.replaces(relative_path));
+			}
+			for relative_path in layer.get_files(relative_dir, filter, depth) {
+				relative_path_to_layer.insert(relative_path, index);
+			}
+		}
+		let mut files: Vec<DataFile> = relative_path_to_layer
+			.into_iter()
+			.map(|(relative_path, index)| self.layers[index].get_file(&relative_path))
+			.collect();
+		files.sort_by(|a, b| a.relative_path.cmp(&b.relative_path));
+		return files;
+	}
+
+	/// The file that wins at relative_path, if any layer has it
+	pub fn resolve(&self, relative_path: &Path) -> Option<DataFile> {
+		for layer in self.layers.iter().rev() {
+			if layer.has_file(relative_path) {
+				return Some(layer.get_file(relative_path));
+			}
+			if layer.replaces(relative_path) {
+				return None;
+			}
+		}
+		return None;
+	}
+
+	/// Every version of the file at relative_path that is not hidden by a replace_path, in load order
+	///
+	/// Used for files we merge ourselves instead of letting the last one win (eg flags/colors.txt)
+	pub fn get_layered_files(&self, relative_path: &Path) -> Vec<DataFile> {
+		let mut files = vec![];
+		for layer in self.layers.iter() {
+			if layer.replaces(relative_path) {
+				files.clear();
+			}
+			if layer.has_file(relative_path) {
+				files.push(layer.get_file(relative_path));
+			}
+		}
+		return files;
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use std::io::Write;
+	use tempfile::TempDir;
+
+	fn create_files(root: &Path, files: &[&str]) {
+		for file in files {
+			let path = root.join(file);
+			fs::create_dir_all(path.parent().unwrap()).unwrap();
+			fs::write(path, "").unwrap();
+		}
+	}
+
+	fn relative_paths(files: Vec<DataFile>) -> Vec<String> {
+		return files
+			.iter()
+			.map(|f| f.relative_path.to_string_lossy().replace('\\', "/"))
+			.collect();
+	}
+
+	fn yml() -> FileFilter {
+		return FileFilter::Extension(OsString::from("yml"));
+	}
+
+	#[test]
+	fn test_later_layer_overrides_same_relative_path() {
+		let vanilla = TempDir::new().unwrap();
+		let mod_dir = TempDir::new().unwrap();
+		create_files(vanilla.path(), &["loc/a.yml", "loc/b.yml"]);
+		create_files(mod_dir.path(), &["loc/b.yml", "loc/c.yml"]);
+		let data_fs = DataFs::new(vec![
+			DataLayer::new(vanilla.path().to_path_buf(), vec![]),
+			DataLayer::new(mod_dir.path().to_path_buf(), vec![]),
+		]);
+		let files = data_fs.get_files(Path::new("loc"), &yml(), 8);
+		assert_eq!(files[0].root, vanilla.path());
+		assert_eq!(files[1].root, mod_dir.path());
+		assert_eq!(files[2].root, mod_dir.path());
+		assert_eq!(
+			relative_paths(files),
+			vec!["loc/a.yml", "loc/b.yml", "loc/c.yml"]
+		);
+	}
+
+	#[test]
+	fn test_replace_path_hides_earlier_files_but_not_sub_dirs() {
+		let vanilla = TempDir::new().unwrap();
+		let mod_dir = TempDir::new().unwrap();
+		create_files(
+			vanilla.path(),
+			&["loc/a.yml", "loc/sub/b.yml", "other/c.yml"],
+		);
+		create_files(mod_dir.path(), &["loc/d.yml"]);
+		let data_fs = DataFs::new(vec![
+			DataLayer::new(vanilla.path().to_path_buf(), vec![]),
+			DataLayer::new(mod_dir.path().to_path_buf(), vec![PathBuf::from("loc")]),
+		]);
+		assert_eq!(
+			relative_paths(data_fs.get_files(Path::new("loc"), &yml(), 8)),
+			vec!["loc/d.yml", "loc/sub/b.yml"]
+		);
+		assert_eq!(data_fs.resolve(Path::new("loc/a.yml")), None);
+		assert!(data_fs.resolve(Path::new("loc/sub/b.yml")).is_some());
+		assert!(data_fs.resolve(Path::new("other/c.yml")).is_some());
+	}
+
+	#[test]
+	fn test_replace_path_does_not_hide_later_layers() {
+		let vanilla = TempDir::new().unwrap();
+		let replacing_mod = TempDir::new().unwrap();
+		let later_mod = TempDir::new().unwrap();
+		create_files(vanilla.path(), &["flags/colors.txt"]);
+		create_files(replacing_mod.path(), &["flags/colors.txt"]);
+		create_files(later_mod.path(), &["flags/colors.txt"]);
+		let data_fs = DataFs::new(vec![
+			DataLayer::new(vanilla.path().to_path_buf(), vec![]),
+			DataLayer::new(
+				replacing_mod.path().to_path_buf(),
+				vec![PathBuf::from("flags")],
+			),
+			DataLayer::new(later_mod.path().to_path_buf(), vec![]),
+		]);
+		let layered: Vec<PathBuf> = data_fs
+			.get_layered_files(Path::new("flags/colors.txt"))
+			.into_iter()
+			.map(|f| f.root)
+			.collect();
+		assert_eq!(
+			layered,
+			vec![
+				replacing_mod.path().to_path_buf(),
+				later_mod.path().to_path_buf()
+			]
+		);
+		assert_eq!(
+			data_fs.resolve(Path::new("flags/colors.txt")).unwrap().root,
+			later_mod.path()
+		);
+	}
+
+	#[test]
+	fn test_archive_layer() {
+		let vanilla = TempDir::new().unwrap();
+		let workshop = TempDir::new().unwrap();
+		create_files(vanilla.path(), &["loc/a.yml", "loc/b.yml"]);
+		let archive = workshop.path().join("mod.zip");
+		let mut zip = zip::ZipWriter::new(fs::File::create(&archive).unwrap());
+		zip.add_directory("loc/", Default::default()).unwrap();
+		for (name, content) in [("loc/b.yml", "zipped b"), ("loc/sub/c.yml", "zipped c")] {
+			zip.start_file(name, Default::default()).unwrap();
+			zip.write_all(content.as_bytes()).unwrap();
+		}
+		zip.finish().unwrap();
+
+		let data_fs = DataFs::new(vec![
+			DataLayer::new(vanilla.path().to_path_buf(), vec![]),
+			DataLayer::from_archive(archive.clone(), vec![]).unwrap(),
+		]);
+		let files = data_fs.get_files(Path::new("loc"), &yml(), 1);
+		assert!(!files[0].in_archive);
+		assert!(files[1].in_archive);
+		assert_eq!(files[1].read_to_string().unwrap(), "zipped b");
+		assert_eq!(relative_paths(files), vec!["loc/a.yml", "loc/b.yml"]);
+		assert_eq!(
+			relative_paths(data_fs.get_files(Path::new("loc"), &yml(), 2)),
+			vec!["loc/a.yml", "loc/b.yml", "loc/sub/c.yml"]
+		);
+		let resolved = data_fs.resolve(Path::new("loc/sub/c.yml")).unwrap();
+		assert_eq!(resolved.root, archive);
+		assert_eq!(resolved.read_to_string().unwrap(), "zipped c");
+	}
+}
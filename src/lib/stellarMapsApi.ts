@@ -20,23 +20,158 @@ const stellarMapsApi = {
 			filter: get(debug) ? true : gameStateFilter,
 		});
 	},
+	exportSqlite(path: string, outputPath: string): Promise<void> {
+		return invoke('export_sqlite_cmd', { path, outputPath });
+	},
+	exportCountryStats(path: string, outputPath: string): Promise<void> {
+		return invoke('export_country_stats_cmd', { path, outputPath });
+	},
+	exportCampaignStats(dir: string, outputPath: string): Promise<void> {
+		return invoke('export_campaign_stats_cmd', { dir, outputPath });
+	},
+	loadCampaignHistory(dir: string): Promise<StellarisCampaignHistory> {
+		return invoke('get_campaign_history_cmd', { dir });
+	},
+	loadShortestPath(
+		path: string,
+		from: number,
+		to: number,
+		metric: StellarisGraphMetric,
+		includeBypasses: boolean,
+	): Promise<StellarisGraphPath | null> {
+		return invoke('get_shortest_path_cmd', { path, from, to, metric, includeBypasses });
+	},
+	loadSystemDistances(
+		path: string,
+		from: number[],
+		metric: StellarisGraphMetric,
+		includeBypasses: boolean,
+	): Promise<Record<number, number>> {
+		return invoke('get_system_distances_cmd', { path, from, metric, includeBypasses });
+	},
+	loadCapitalDistances(
+		path: string,
+		countryId: number,
+		metric: StellarisGraphMetric,
+		includeBypasses: boolean,
+	): Promise<Record<number, number>> {
+		return invoke('get_capital_distances_cmd', { path, countryId, metric, includeBypasses });
+	},
+	loadChokepoints(path: string, includeBypasses: boolean): Promise<StellarisChokepoints> {
+		return invoke('get_chokepoints_cmd', { path, includeBypasses });
+	},
+	loadComponents(path: string, includeBypasses: boolean): Promise<number[][]> {
+		return invoke('get_components_cmd', { path, includeBypasses });
+	},
+	loadBorderReports(path: string, includeBypasses: boolean): Promise<StellarisBorderReport[]> {
+		return invoke('get_border_reports_cmd', { path, includeBypasses });
+	},
+	loadFleetMovements(path: string): Promise<StellarisFleetMovement[]> {
+		return invoke('get_fleet_movements_cmd', { path });
+	},
+	loadDiplomacy(path: string): Promise<StellarisDiplomacy> {
+		return invoke('get_diplomacy_cmd', { path });
+	},
+	loadGalacticCommunity(path: string): Promise<StellarisGalacticCommunity | null> {
+		return invoke('get_galactic_community_cmd', { path });
+	},
+	loadDemographics(path: string, savePath: string): Promise<StellarisDemographicsReport> {
+		return invoke('get_demographics_cmd', { path, savePath });
+	},
+	loadEconomy(path: string): Promise<StellarisEconomySnapshot[]> {
+		return invoke('get_economy_cmd', { path });
+	},
+	exportEconomy(path: string, outputPath: string): Promise<void> {
+		return invoke('export_economy_cmd', { path, outputPath });
+	},
 	loadFonts(): Promise<string[]> {
 		return invoke('get_fonts_cmd');
 	},
+	loadFont(
+		family: string,
+		weight?: number,
+		style?: 'normal' | 'italic' | 'oblique',
+		text?: string,
+	): Promise<StellarisFontData> {
+		return invoke('get_font_cmd', { family, weight, style, text });
+	},
+	checkGlyphCoverage(family: string, strings: string[]): Promise<StellarisGlyphCoverage> {
+		return invoke('get_glyph_coverage_cmd', { family, strings });
+	},
+	loadConfig(): Promise<StellarMapsConfig> {
+		return invoke('get_config_cmd');
+	},
+	saveConfig(config: StellarMapsConfig): Promise<void> {
+		return invoke('set_config_cmd', { config });
+	},
 	revealFile(path: string): Promise<void> {
 		return invoke('reveal_file_cmd', { path });
 	},
-	loadColors(path: string): Promise<string[]> {
-		return invoke('get_stellaris_colors_cmd', { path });
+	loadColors(path: string, savePath?: string): Promise<string[]> {
+		return invoke('get_stellaris_colors_cmd', { path, savePath });
+	},
+	loadLoc(path: string, language?: string, savePath?: string): Promise<Record<string, string>> {
+		return invoke('get_stellaris_loc_cmd', { path, language, savePath });
 	},
-	loadLoc(path: string, language: string): Promise<Record<string, string>> {
-		return invoke('get_stellaris_loc_cmd', { path, language });
+	loadLocWithFallbacks(
+		path: string,
+		language: string | undefined,
+		fallbackLanguages: string[],
+		savePath?: string,
+	): Promise<StellarisLoc> {
+		return invoke('get_stellaris_loc_with_fallbacks_cmd', {
+			path,
+			language,
+			fallbackLanguages,
+			savePath,
+		});
 	},
 	loadStellarisInstallDir(): Promise<string> {
 		return invoke('get_stellaris_install_dir_cmd');
 	},
-	loadEmblem(path: string, category: string, file: string): Promise<string> {
-		return invoke('get_emblem_cmd', { path, category, file });
+	loadStellarisInstallDirCandidates(): Promise<StellarisInstallDirCandidate[]> {
+		return invoke('get_stellaris_install_dir_candidates_cmd');
+	},
+	loadStellarisDataRoots(): Promise<StellarisDataRoot[]> {
+		return invoke('get_stellaris_data_roots_cmd');
+	},
+	loadEmblem(
+		path: string,
+		category: string,
+		file: string,
+		savePath?: string,
+		size?: number,
+		variant?: 'silhouette' | 'outline',
+	): Promise<string> {
+		return invoke('get_emblem_cmd', { path, category, file, savePath, size, variant });
+	},
+	loadEmblemAtlas(
+		path: string,
+		emblems: [category: string, file: string][],
+		savePath?: string,
+	): Promise<StellarisEmblemAtlas> {
+		return invoke('get_emblem_atlas_cmd', { path, emblems, savePath });
+	},
+	loadFlag(path: string, flag: StellarisFlag, size?: number, savePath?: string): Promise<string> {
+		return invoke('get_flag_cmd', { path, flag, size, savePath });
+	},
+	loadEmblemCategories(path: string, savePath?: string): Promise<StellarisEmblemCategory[]> {
+		return invoke('get_emblems_cmd', { path, savePath });
+	},
+	loadTexture(
+		path: string,
+		kind: 'planet_class' | 'star_class' | 'portrait',
+		key: string,
+		size?: number,
+		savePath?: string,
+	): Promise<string> {
+		return invoke('get_texture_cmd', { path, kind, key, size, savePath });
+	},
+	loadEnabledMods(): Promise<StellarisMod[]> {
+		return invoke('get_enabled_mods_cmd');
+	},
+	loadSaveMods(path: string): Promise<StellarisSaveMods> {
+		return invoke('get_stellaris_save_mods_cmd', { path });
 	},
 };
 export default stellarMapsApi;
@@ -47,3 +182,245 @@ export interface StellarisSaveMetadata {
 	path: string;
 	modified: number;
 }
+
+export interface StellarisLoc {
+	locs: Record<string, string>;
+	fallbacks: {
+		path: string;
+		language: string;
+		count: number;
+	}[];
+}
+
+export interface StellarisEmblemAtlas {
+	atlas: string;
+	/** keyed by `${category}/${file}` */
+	index: Record<string, { x: number; y: number; width: number; height: number }>;
+	missing: string[];
+}
+
+export interface StellarisEmblemCategory {
+	name: string;
+	emblems: {
+		file: string;
+		/** the data dir or zipped mod the emblem is loaded from */
+		source: string;
+	}[];
+}
+
+export interface StellarisFlag {
+	icon?: { category: string; file: string } | null;
+	background?: { category: string; file: string } | null;
+	colors: string[];
+}
+
+export interface StellarisCampaignHistory {
+	dates: string[];
+	/** every series has one entry per date, null where the country didn't exist */
+	countries: {
+		country_id: number;
+		name: string | null;
+		flag: StellarisFlag | null;
+		systems: (number | null)[];
+		pops: (number | null)[];
+		military_power: (number | null)[];
+		rank: (number | null)[];
+	}[];
+}
+
+/** jumps count every link as 1; length uses hyperlane lengths, with bypasses counting as 0 */
+export type StellarisGraphMetric = 'jumps' | 'length';
+
+export type StellarisLinkKind = 'hyperlane' | 'wormhole' | 'gateway' | 'lgate' | 'shroud_tunnel';
+
+export interface StellarisGraphPath {
+	systems: number[];
+	links: StellarisLinkKind[];
+	jumps: number;
+	length: number;
+}
+
+export interface StellarisChokepoints {
+	articulation_points: number[];
+	bridges: [number, number][];
+}
+
+export interface StellarisBorderReport {
+	country_id: number;
+	name: string | null;
+	systems: number;
+	border_systems: { system_id: number; neighbors: number[]; touches_unowned: boolean }[];
+	/** systems whose loss would cut the territory in two or more */
+	chokepoints: number[];
+	/** keyed by the country owning the other end */
+	entry_lanes: Record<number, number>;
+	unowned_entry_lanes: number;
+}
+
+/** galactic coordinates as saved; x is inverted for display */
+export interface StellarisSystemPoint {
+	system_id: number;
+	x: number;
+	y: number;
+}
+
+export interface StellarisFleetMovement {
+	fleet_id: number;
+	name: string | null;
+	owner: number | null;
+	military_power: number;
+	num_ships: number;
+	system: StellarisSystemPoint | null;
+	target: StellarisSystemPoint | null;
+	/** the systems still ahead, in order */
+	path: (StellarisSystemPoint & { ftl: string | null })[];
+	state: string | null;
+}
+
+export interface StellarisDiplomacy {
+	wars: {
+		war_id: number;
+		name: string | null;
+		start_date: string | null;
+		attackers: number[];
+		defenders: number[];
+		attacker_war_goal: string | null;
+		defender_war_goal: string | null;
+		/** 0 to 1 */
+		attacker_war_exhaustion: number | null;
+		defender_war_exhaustion: number | null;
+		battles: {
+			date: string | null;
+			system_id: number | null;
+			planet_id: number | null;
+			type: string | null;
+			attackers: number[];
+			defenders: number[];
+			attacker_victory: boolean | null;
+			attacker_losses: number | null;
+			defender_losses: number | null;
+		}[];
+	}[];
+	federations: {
+		federation_id: number;
+		name: string | null;
+		type: string;
+		leader: number | null;
+		members: number[];
+		associates: number[];
+	}[];
+	subjects: { overlord: number; subject: number; status: string | null }[];
+	/** how owner sees country; usually present in both directions */
+	relations: {
+		owner: number;
+		country: number;
+		opinion: number;
+		trust: number | null;
+		statuses: string[];
+		wars: number[];
+	}[];
+}
+
+export interface StellarisResolution {
+	resolution_id: number | null;
+	type: string | null;
+	date: string | null;
+	proposer: number | null;
+}
+
+export interface StellarisGalacticCommunity {
+	members: number[];
+	council: number[];
+	custodian: number | null;
+	emperor: number | null;
+	voting: StellarisResolution | null;
+	proposed: StellarisResolution[];
+	/** oldest first */
+	passed: StellarisResolution[];
+}
+
+export interface StellarisDemographics {
+	total: number;
+	/** keyed by species id */
+	species: Record<number, number>;
+	ethics: Record<string, number>;
+	strata: Record<string, number>;
+	/** employed pops, keyed by job category, eg "specialist" */
+	job_categories: Record<string, number>;
+}
+
+export interface StellarisDemographicsReport {
+	species: Record<number, { name: string | null; class: string | null }>;
+	/** keyed by system id */
+	systems: Record<number, StellarisDemographics>;
+	/** keyed by country id, by planet owner */
+	countries: Record<number, StellarisDemographics>;
+}
+
+export interface StellarisEconomySnapshot {
+	country_id: number;
+	name: string | null;
+	/** keyed by resource, eg "energy" */
+	stockpiles: Record<string, number>;
+	/** monthly, keyed by resource */
+	income: Record<string, number>;
+	expenses: Record<string, number>;
+	/** the largest expense categories of each resource, largest first */
+	top_expenses: Record<string, { category: string; amount: number }[]>;
+}
+
+export interface StellarisFontData {
+	family: string;
+	postscript_name: string | null;
+	format: 'truetype' | 'opentype';
+	/** base64 encoded font file */
+	data: string;
+}
+
+export interface StellarisGlyphCoverage {
+	family: string;
+	missing: string[];
+	affected: string[];
+	fallbacks: { family: string; covered: number }[];
+}
+
+export interface StellarisMod {
+	name: string;
+	descriptor: string | null;
+	path: string | null;
+	archive: string | null;
+	remote_file_id: string | null;
+	replace_paths: string[];
+}
+
+export interface StellarisSaveMods {
+	resolved: StellarisMod[];
+	missing: string[];
+}
+
+export interface StellarisInstallDirCandidate {
+	path: string;
+	reason: string;
+	valid: boolean;
+}
+
+export interface StellarisDataRoot {
+	kind: 'user_data' | 'saves';
+	path: string;
+	reason: string;
+	exists: boolean;
+	found: string[];
+}
+
+export interface StellarMapsConfig {
+	install_path: string | null;
+	user_data_path: string | null;
+	save_dirs: string[];
+	extra_mod_dirs: string[];
+	language: string | null;
+	fallback_languages: string[];
+	cache: {
+		enabled: boolean;
+		dir: string | null;
+	};
+}